@@ -1,7 +1,7 @@
-use crate::MAX_FIELD_EXT;
-#[allow(unused)]
-use crate::field_extensions::{F2_i, F3_i};
-use crate::polynomials::{Term, Polynomial};
+use crate::field_extensions::{ClosedPointIterator, FieldTraits, embed_basis};
+use crate::lookup_table::{closed_point_count, PackedTable};
+use crate::polynomials::{field_order, Term, Polynomial};
+use crate::zeta::LPolynomial;
 
 
 // 
@@ -11,52 +11,254 @@ use crate::polynomials::{Term, Polynomial};
 // 
 
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct PolynomialResult {
-  pub poly: IsoPolynomial,
-  pub points_on_curve: [usize; MAX_FIELD_EXT],
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolynomialResult<const W: usize> {
+  pub poly: IsoPolynomial<W>,
+  pub points_on_curve: Vec<usize>,
+  // Only known once there are at least genus many point counts
+  pub l_polynomial: Option<LPolynomial>,
 }
 
-impl PolynomialResult {
-  pub fn new(iso_poly: IsoPolynomial, points_on_curve: [usize; MAX_FIELD_EXT]) -> PolynomialResult {
-    PolynomialResult { poly: iso_poly, points_on_curve }
+impl<const W: usize> PolynomialResult<W> {
+  pub fn new(iso_poly: IsoPolynomial<W>, points_on_curve: Vec<usize>) -> PolynomialResult<W> {
+    PolynomialResult { poly: iso_poly, points_on_curve, l_polynomial: None }
+  }
+
+  // Only meaningful for singular classes, whose points stop at the last extension before the singular one
+  pub fn first_singular_extension(&self) -> usize {
+    self.points_on_curve.len() + 1
   }
 
-  pub fn to_string(&self, normal: &Vec<Term>) -> String {
-    format!("{} | {} | {:?}", self.poly.representative.str(normal), self.poly.size, self.points_on_curve)
+  pub fn compute_l_polynomial<B: FieldTraits>(&mut self, genus: usize) {
+    self.l_polynomial = LPolynomial::from_point_counts(field_order::<B>(), genus, &self.points_on_curve);
   }
+
+  // Appends the point counts predicted by the L-polynomial up to F_q^count_ext
+  // Config keeps the Weil bound of count_ext within 64 bits, so the counts fit unless the L-polynomial is not of a curve
+  pub fn extend_points_on_curve(&mut self, count_ext: usize) -> Result<(), String> {
+    if let Some(l_polynomial) = &self.l_polynomial {
+      for k in self.points_on_curve.len() + 1..=count_ext {
+        let points = l_polynomial.point_count(k).and_then(|points| usize::try_from(points).ok());
+        self.points_on_curve.push(points.ok_or(format!("predicted point count over F_q^{} does not fit in a usize", k))?);
+      }
+    }
+    Ok(())
+  }
+
+  pub fn to_string<B: FieldTraits>(&self, normal: &[Term]) -> String {
+    let l_polynomial = self.l_polynomial.as_ref().map_or("-".to_owned(), |l| l.str());
+    format!("{} | {} | {:?} | {}", self.poly.representative.str::<B>(normal), self.poly.size, self.points_on_curve, l_polynomial)
+  }
+}
+
+// How the coefficients of a packed polynomial over the base field B become elements of F
+pub struct BaseEmbedding<F: FieldTraits> {
+  // Bits per coefficient in the packed polynomial
+  pub coeff_bits: usize,
+  // Images of 1, w, ..., w^(r-1), see field_extensions::embed_basis
+  pub basis: Vec<F>,
 }
 
-// FIELD_ORDER_PROBLEM
-pub struct Lookup <const N: u8> {
-  pub normal: Vec<Vec<F2_i<N>>>,
-  pub part_x: Vec<Vec<F2_i<N>>>,
-  pub part_y: Vec<Vec<F2_i<N>>>,
-  pub part_z: Vec<Vec<F2_i<N>>>,
-} 
+impl<F: FieldTraits> BaseEmbedding<F> {
+  pub fn new<B: FieldTraits>() -> BaseEmbedding<F> {
+    BaseEmbedding { coeff_bits: B::PACKED_BITS, basis: embed_basis::<B, F>() }
+  }
+
+  // The image in F of a single coefficient, given as the bits of an element of B
+  pub fn embed(&self, bits: u64) -> F {
+    let digit_mask = (1 << F::COEFF_BITS) - 1;
+    self.basis.iter().enumerate().fold(F::ZERO, |acc, (j, &b)| acc + b.scale((bits >> (F::COEFF_BITS * j)) & digit_mask))
+  }
+}
+
+
+// All monomials (and their derivatives) evaluated in every closed point of exact degree k over F_q, with F = F_q^k
+// Points over subfields were already checked by the lookups below, and F has its coefficients in F_q,
+// so a closed point is singular as soon as one of its Frobenius conjugates is
+// Row i of the table belongs to the i-th point of F::iterate_over_closed_points(q, k)
+// A table over the memory budget is not stored, blocks of block_rows rows are generated whenever they are needed
+// Its points are not stored either, only an iterator at the start of every block, so a block finds its points again
+pub struct Lookup<F: FieldTraits> {
+  pub table: Option<PackedTable<F>>,
+  // The terms of the columns: the monomials, then their derivatives to x, y and z
+  pub terms: Vec<Term>,
+  pub block_rows: usize,
+  pub embedding: BaseEmbedding<F>,
+  // Rows from here on belong to points with z = 1, the points on the line z = 0 come first
+  pub affine_start: usize,
+  // The point of every row of a stored table, streamed lookups resume the iterator of a block instead
+  pub points: Vec<(F, F, F)>,
+  pub block_starts: Vec<ClosedPointIterator<F>>,
+  pub rows: usize,
+  // The degree k of F over F_q
+  pub k: usize,
+}
+
+// Size of a generated block, small enough to stay in the cache of a core
+const BLOCK_BYTES: usize = 1 << 18;
+
+impl<F: FieldTraits> Lookup<F> {
+  // Taken from the cache directory when it has valid tables, and written to it otherwise
+  // Streamed instead when the table takes more than budget bytes
+  pub fn create<B: FieldTraits>(normal: &[Term], part_x: &[Term], part_y: &[Term], part_z: &[Term], cache: Option<&str>, budget: Option<usize>) -> Lookup<F> {
+    let k = F::DEGREE / B::DEGREE;
+    let terms = [normal, part_x, part_y, part_z].concat();
+    let rows = closed_point_count(field_order::<B>(), k);
+    let stream = budget.is_some_and(|b| PackedTable::<F>::size(rows, terms.len()) + rows * size_of::<(F, F, F)>() > b);
+
+    if let Some(lookup) = cache.filter(|_| !stream).and_then(|dir| Lookup::load::<B>(dir, &terms)) {
+      println!("Loaded lookup tables for degree {} ({} closed points) from the cache", F::DEGREE, lookup.rows);
+      return lookup
+    }
+
+    let block_rows = (8 * BLOCK_BYTES / (terms.len() * PackedTable::<F>::BITS)).max(1);
+    if stream {
+      println!("Streaming lookup tables for degree {} ({} closed points) in blocks of {} points", F::DEGREE, rows, block_rows);
+      return Lookup::streamed::<B>(terms, rows, block_rows)
+    }
+
+    let points: Vec<(F, F, F)> = F::iterate_over_closed_points(field_order::<B>(), k).collect();
+    let affine_start = points.iter().position(|p| p.2 == F::ONE).unwrap_or(points.len());
+    let table = PackedTable::generate(&terms, &points);
+    println!("Made lookup tables for degree {} ({} closed points)", F::DEGREE, rows);
 
-impl<const N: u8> Lookup<N> {
-  pub fn create(normal: &Vec<Term>, part_x: &Vec<Term>, part_y: &Vec<Term>, part_z: &Vec<Term>) -> Lookup<N> {
-    let n_res = Term::generate_points_for_multiple(&normal);
-    let x_res = Term::generate_points_for_multiple(&part_x);
-    let y_res = Term::generate_points_for_multiple(&part_y);
-    let z_res = Term::generate_points_for_multiple(&part_z);
-    println!("Made lookup tables for degree {N}");
+    let lookup = Lookup { table: Some(table), terms, block_rows, embedding: BaseEmbedding::new::<B>(), affine_start, points, block_starts: vec![], rows, k };
+    if let Some(dir) = cache {
+      lookup.save::<B>(dir);
+    }
+    lookup
+  }
+
+  // Walks the points once to find where every block starts
+  fn streamed<B: FieldTraits>(terms: Vec<Term>, rows: usize, block_rows: usize) -> Lookup<F> {
+    let mut iterator = F::iterate_over_closed_points(field_order::<B>(), F::DEGREE / B::DEGREE);
+    let mut block_starts = vec![];
+    let mut affine_start = rows;
+    for row in 0..rows {
+      if row % block_rows == 0 {
+        block_starts.push(iterator.clone());
+      }
+      let point = iterator.next().expect("Fewer closed points than closed_point_count");
+      if point.2 == F::ONE {
+        affine_start = affine_start.min(row);
+      }
+    }
+    Lookup { table: None, terms, block_rows, embedding: BaseEmbedding::new::<B>(), affine_start, points: vec![], block_starts, rows, k: F::DEGREE / B::DEGREE }
+  }
 
-    Lookup { normal: n_res, part_x: x_res, part_y: y_res, part_z: z_res }
+  pub fn monomials(&self) -> usize {
+    self.terms.len() / 4
+  }
+
+  // Bytes the lookup keeps in memory for its table and points
+  pub fn memory(&self) -> usize {
+    let table = self.table.as_ref().map_or(0, |t| PackedTable::<F>::size(t.rows, t.columns));
+    table + self.points.len() * size_of::<(F, F, F)>() + self.block_starts.len() * size_of::<ClosedPointIterator<F>>()
+  }
+
+  // Calls f with the first row, the points and the table of consecutive blocks of rows, until f returns false
+  // That is the whole table at once, or generated blocks when streaming
+  // Streaming enumerates the points of a block again on every call, which costs more than evaluating the terms in them
+  pub fn for_each_block(&self, mut f: impl FnMut(usize, &[(F, F, F)], &PackedTable<F>) -> bool) {
+    match &self.table {
+      Some(table) => {
+        f(0, &self.points, table);
+      },
+      None => {
+        for (i, iterator) in self.block_starts.iter().enumerate() {
+          let start = i * self.block_rows;
+          let points: Vec<(F, F, F)> = iterator.clone().take(self.block_rows.min(self.rows - start)).collect();
+          if !f(start, &points, &PackedTable::generate(&self.terms, &points)) {
+            return
+          }
+        }
+      },
+    }
   }
 }
 
 
 
+// Sizes of the singular classes added up per first extension with a singular point, k_i at index i - 1
+pub fn first_singular_histogram<const W: usize>(singular: &[PolynomialResult<W>], enumerated_ext: usize) -> Vec<usize> {
+  let mut histogram = vec![0; enumerated_ext];
+  for result in singular {
+    histogram[result.first_singular_extension() - 1] += result.poly.size as usize;
+  }
+  histogram
+}
+
+
 #[derive(Debug, Copy, Clone, PartialEq)]
-pub struct IsoPolynomial {
-  pub representative: Polynomial,
+pub struct IsoPolynomial<const W: usize> {
+  pub representative: Polynomial<W>,
   pub size: u32,
 }
 
-impl IsoPolynomial {
-  pub fn deconstruct(self) -> (Polynomial, u32) {
-    (self.representative, self.size)
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::extension_ladder::ExtensionLadder;
+  use crate::field_extensions::{F2, F2_i, F3_i};
+  use crate::orbits;
+
+  // Cubics over F2 up to k_3, the results of the singular classes
+  fn singular_cubics_over_f2() -> Vec<PolynomialResult<1>> {
+    let normal = Polynomial::<1>::generate_default_lut(3);
+    let (part_x, part_y, part_z) = Polynomial::<1>::generate_derative_luts(&normal, 2);
+    let config = Config::from_args("--degree 3 --field 2 --input unused --fresh".split_whitespace().map(str::to_owned)).unwrap();
+    let ladder = ExtensionLadder::<1>::create::<F2>(&config, &normal, &part_x, &part_y, &part_z);
+    let classes = orbits::generate::<F2_i<1>, 1>(&normal);
+    let polys: Vec<Polynomial<1>> = classes.iter().map(|c| c.representative).collect();
+    classes.into_iter().zip(ladder.points_on_curve(&polys))
+      .map(|(class, points)| PolynomialResult::new(class, points))
+      .filter(|r| r.points_on_curve.len() < 3)
+      .collect()
+  }
+
+  // Of the 1023 cubics over F2, 336 are smooth. A singular point that is not rational makes the cubic reducible:
+  // a line times one of the 28 smooth conics it misses over F2, singular in a point of degree 2,
+  // or one of the 8 triangles of conjugate lines over F8 that do not meet in a rational point, singular in a point of degree 3
+  #[test]
+  fn first_singular_of_cubics_over_f2() {
+    let singular = singular_cubics_over_f2();
+    assert_eq!(first_singular_histogram(&singular, 3), vec![1023 - 336 - 28 - 8, 28, 8]);
+  }
+
+  // x y z is singular over every extension, its first singular extension is k_1
+  #[test]
+  fn first_singular_extension_is_the_first() {
+    let normal = Polynomial::<1>::generate_default_lut(3);
+    let xyz = singular_cubics_over_f2().into_iter().find(|r| r.poly.representative.str::<F2_i<1>>(&normal) == "1_111").unwrap();
+    assert_eq!(xyz.first_singular_extension(), 1);
+    // Counts up to k_1 only, so the ladder stopped at k_2
+    let stopped_at_k2 = PolynomialResult::new(xyz.poly, vec![3]);
+    assert_eq!(stopped_at_k2.first_singular_extension(), 2);
   }
-}
\ No newline at end of file
+
+  // Blocks that do not divide the rows, resumed from their iterators, give the rows of the stored table
+  #[test]
+  fn streamed_blocks_match_stored_table() {
+    let normal = Polynomial::<1>::generate_default_lut(3);
+    let (part_x, part_y, part_z) = Polynomial::<1>::generate_derative_luts(&normal, 3);
+    let stored = Lookup::<F3_i<4>>::create::<F3_i<1>>(&normal, &part_x, &part_y, &part_z, None, None);
+    let streamed = Lookup::<F3_i<4>>::streamed::<F3_i<1>>(stored.terms.clone(), stored.rows, 11);
+    assert_eq!(streamed.affine_start, stored.affine_start);
+    assert!(!stored.rows.is_multiple_of(11) && streamed.memory() < stored.memory());
+
+    let table = stored.table.as_ref().unwrap();
+    let mut rows = 0;
+    streamed.for_each_block(|start, points, block| {
+      assert_eq!(start, rows);
+      assert_eq!(points, &stored.points[start..start + points.len()]);
+      for row in 0..block.rows {
+        assert!((0..block.columns).all(|c| block.row(row).get(c) == table.row(start + row).get(c)));
+      }
+      rows += block.rows;
+      true
+    });
+    assert_eq!(rows, stored.rows);
+  }
+}
@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+
+use crate::algebraic_types::IsoPolynomial;
+use crate::field_extensions::FieldTraits;
+use crate::matrix::Matrix3;
+use crate::orbits::{self, FieldTables, LinearAction};
+use crate::validation;
+use crate::polynomials::Term;
+
+
+//
+//
+// Projective automorphism groups of polynomials, so the stabilizers in PGL3(F_q)
+// A matrix M fixes the class of f when f(M (x, y, z)) = c f for some nonzero scalar c
+// By orbit-stabilizer the isomorphism class of f then has |PGL3(F_q)| / |Stab(f)| elements
+//
+//
+
+
+// Above this order we only report the order, determining the structure takes |G|^2 multiplications
+const MAX_STRUCTURE_ORDER: usize = 1000;
+// Largest amount of operations of the stabilizer check, which expands the actions through the field arithmetic instead of tables
+// At about 8 nanoseconds each (cubics over F7 take 50 seconds) this is a few minutes, far below orbits::MAX_OPERATIONS
+const MAX_OPERATIONS: u128 = 1 << 34;
+
+
+pub struct Stabilizer<B: FieldTraits> {
+  pub elements: Vec<Matrix3<B>>,
+}
+
+impl<B: FieldTraits> Stabilizer<B> {
+  pub fn order(&self) -> usize {
+    self.elements.len()
+  }
+
+  // Order of g as an element of PGL3, so up to scalars
+  fn element_order(g: Matrix3<B>) -> usize {
+    let identity = Matrix3::identity();
+    let mut power = g.normalize();
+    let mut order = 1;
+    while power != identity {
+      power = (power * g).normalize();
+      order += 1;
+    }
+    order
+  }
+
+  // Cyclic groups are recognised by a generator, otherwise we give the element orders and whether the group is abelian
+  pub fn structure(&self) -> String {
+    let order = self.order();
+    if order == 1 {
+      return "trivial".to_owned()
+    }
+    if order > MAX_STRUCTURE_ORDER {
+      return "not determined".to_owned()
+    }
+
+    let mut element_orders = BTreeMap::new();
+    for &g in &self.elements {
+      *element_orders.entry(Self::element_order(g)).or_insert(0) += 1;
+    }
+    if element_orders.contains_key(&order) {
+      return format!("C{}", order)
+    }
+
+    let abelian = self.elements.iter().all(|&g| self.elements.iter().all(|&h| (g * h).normalize() == (h * g).normalize()));
+    let element_orders: Vec<String> = element_orders.iter().map(|(o, amount)| format!("{}x{}", amount, o)).collect();
+    format!("{}, element orders {}", if abelian { "abelian" } else { "non-abelian" }, element_orders.join(" "))
+  }
+}
+
+
+// The stabilizers of all polynomials at once, so every matrix only gets expanded into a linear action once
+pub fn stabilizers<B: FieldTraits, const W: usize>(iso_polys: &[IsoPolynomial<W>], normal: &[Term]) -> Vec<Stabilizer<B>> {
+  let field = FieldTables::new::<B>();
+  let n = normal.len();
+  let polys: Vec<Vec<u8>> = iso_polys.iter().map(|iso| {
+    let mut constants = orbits::to_constants::<B, W>(&iso.representative, n);
+    orbits::normalize(&field, &mut constants);
+    constants
+  }).collect();
+
+  let mut stabilizers: Vec<Stabilizer<B>> = iso_polys.iter().map(|_| Stabilizer { elements: vec![] }).collect();
+  let mut image = vec![0; n];
+  for m in orbits::pgl3::<B>() {
+    let action = LinearAction::new(&m, normal);
+    for (poly, stabilizer) in polys.iter().zip(stabilizers.iter_mut()) {
+      action.apply(&field, poly, &mut image);
+      orbits::normalize(&field, &mut image);
+      if &image == poly {
+        stabilizer.elements.push(m);
+      }
+    }
+  }
+  stabilizers
+}
+
+// Whether f(M (x, y, z)) is a multiple of f, through Polynomial::substitute rather than the linear actions of orbits
+fn fixes<B: FieldTraits, const W: usize>(m: &Matrix3<B>, iso: &IsoPolynomial<W>, normal: &[Term], field: &FieldTables) -> bool {
+  let n = normal.len();
+  let mut image = orbits::to_constants::<B, W>(&iso.representative.substitute(m, normal), n);
+  let mut poly = orbits::to_constants::<B, W>(&iso.representative, n);
+  orbits::normalize(field, &mut image);
+  orbits::normalize(field, &mut poly);
+  image == poly
+}
+
+// Whether check_sizes finishes. stabilizers walks over all q^9 matrices, expands every element of PGL3 into its action,
+// n * n products of degree linear forms, and applies that to every class, n * n multiply-adds each
+// The classes cover all projective polynomials, so there are at least that many divided by |PGL3|, like validation::check_validation
+pub fn check_stabilizers(field_order: usize, degree: usize) -> Result<(), String> {
+  let n = (degree + 2) * (degree + 1) / 2;
+  let too_many = || format!("Checking the stabilizers of degree {} over F{} takes too many operations to finish", degree, field_order);
+  let total = validation::projective_polynomials(field_order as u128, n).ok_or_else(too_many)?;
+  let order = orbits::pgl3_order(field_order as u64) as u128;
+  let operations = ((n * n) as u128).checked_mul(degree as u128 + total / order + 1)
+    .and_then(|per_element| order.checked_mul(per_element))
+    .and_then(|actions| actions.checked_add((field_order as u128).pow(9)))
+    .ok_or_else(too_many)?;
+  if operations > MAX_OPERATIONS {
+    return Err(format!("Checking the stabilizers of degree {} over F{} takes at least {} operations, too many to finish", degree, field_order, operations))
+  }
+  Ok(())
+}
+
+// Compares the size column of the input with |PGL3(F_q)| / |Stab(f)|, every mismatch is reported
+// Config refuses --check-stabilizers when check_stabilizers fails
+pub fn check_sizes<B: FieldTraits, const W: usize>(iso_polys: &[IsoPolynomial<W>], normal: &[Term], printing: bool) -> Result<(), String> {
+  let pgl3_order = orbits::pgl3_order(crate::polynomials::field_order::<B>());
+  let stabilizers = stabilizers::<B, W>(iso_polys, normal);
+
+  // Spot check of the stabilizers, every element and its inverse fix the polynomial
+  let field = FieldTables::new::<B>();
+  for (iso, stabilizer) in iso_polys.iter().zip(&stabilizers) {
+    for m in &stabilizer.elements {
+      let inverse = m.inverse().expect("Stabilizer element is not invertible");
+      if !fixes(m, iso, normal, &field) || !fixes(&inverse, iso, normal, &field) {
+        return Err(format!("Stabilizer of {} contains a matrix that does not fix it", iso.representative.str::<B>(normal)))
+      }
+    }
+  }
+
+  let mut mismatches = 0;
+  for (iso, stabilizer) in iso_polys.iter().zip(&stabilizers) {
+    let expected = pgl3_order / stabilizer.order() as u64;
+    if printing {
+      println!("{} | stabilizer order {} ({}) | class size {}", iso.representative.str::<B>(normal), stabilizer.order(), stabilizer.structure(), expected);
+    }
+    if expected != iso.size as u64 {
+      eprintln!("Size mismatch for {}: file says {}, stabilizer of order {} gives {}", iso.representative.str::<B>(normal), iso.size, stabilizer.order(), expected);
+      mismatches += 1;
+    }
+  }
+  if mismatches > 0 {
+    return Err(format!("{} isomorphism class sizes in the input file do not match their stabilizers", mismatches))
+  }
+  println!("All {} isomorphism class sizes match their stabilizers", iso_polys.len());
+  Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::field_extensions::{F2_i, F3_i};
+  use crate::parser;
+  use crate::polynomials::Polynomial;
+
+  fn parse<B: FieldTraits>(degree: usize, q: usize, lines: &str) -> (Vec<IsoPolynomial<1>>, Vec<Term>) {
+    let normal = Polynomial::<1>::generate_default_lut(degree);
+    let classes = parser::parse_iso_file::<B, 1>(&format!("{} | {}\n{}", degree, q, lines), degree, q, &normal).unwrap();
+    (classes, normal)
+  }
+
+  // x^3 is fixed by the matrices whose first row is (1, 0, 0), 6 * 4 of them, and xyz by the permutation matrices
+  #[test]
+  fn stabilizers_over_f2() {
+    let (classes, normal) = parse::<F2_i<1>>(3, 2, "1_300 | 7\n1_111 | 28\n");
+    let stabilizers = stabilizers::<F2_i<1>, 1>(&classes, &normal);
+    assert_eq!(stabilizers.iter().map(|s| s.order()).collect::<Vec<_>>(), vec![24, 6]);
+    assert_eq!(stabilizers[1].structure(), "non-abelian, element orders 1x1 3x2 2x3");
+    check_sizes::<F2_i<1>, 1>(&classes, &normal, false).unwrap();
+  }
+
+  // Over F3 the permutations combine with the diagonal matrices of signs up to scalars, 6 * 4 of them
+  #[test]
+  fn stabilizers_over_f3() {
+    let (classes, normal) = parse::<F3_i<1>>(3, 3, "1_111 | 234\n");
+    let stabilizer = &stabilizers::<F3_i<1>, 1>(&classes, &normal)[0];
+    assert_eq!(stabilizer.order(), 24);
+    assert_eq!(orbits::pgl3_order(3) / 24, 234);
+    check_sizes::<F3_i<1>, 1>(&classes, &normal, false).unwrap();
+  }
+
+  #[test]
+  fn wrong_size_is_found() {
+    let (classes, normal) = parse::<F2_i<1>>(3, 2, "1_300 | 8\n");
+    assert_eq!(check_sizes::<F2_i<1>, 1>(&classes, &normal, false), Err("1 isomorphism class sizes in the input file do not match their stabilizers".to_owned()));
+  }
+
+  // Cubics over F7 take under a minute, over F9 there are 42 million elements of PGL3 to expand
+  #[test]
+  fn stabilizer_check_limit() {
+    assert!(check_stabilizers(2, 3).is_ok());
+    assert!(check_stabilizers(7, 3).is_ok());
+    assert!(check_stabilizers(9, 3).is_err());
+    assert!(check_stabilizers(4, 4).is_err());
+    assert!(check_stabilizers(2, 5).is_ok());
+    assert!(check_stabilizers(2, 200).is_err());
+  }
+}
@@ -0,0 +1,86 @@
+//
+//
+// FNV-1a, a small 64 bit hash that is the same on every machine and every run
+// Not cryptographic, it is only meant to tell results and files apart at a glance
+//
+//
+
+
+const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const PRIME: u64 = 0x100000001b3;
+
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fnv1a {
+  hash: u64,
+}
+
+impl Fnv1a {
+  pub fn new() -> Fnv1a {
+    Fnv1a { hash: OFFSET_BASIS }
+  }
+
+  pub fn write(&mut self, bytes: &[u8]) {
+    for &b in bytes {
+      self.hash = (self.hash ^ b as u64).wrapping_mul(PRIME);
+    }
+  }
+
+  pub fn finish(&self) -> u64 {
+    self.hash
+  }
+}
+
+impl Default for Fnv1a {
+  fn default() -> Fnv1a {
+    Fnv1a::new()
+  }
+}
+
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+  let mut hasher = Fnv1a::new();
+  hasher.write(bytes);
+  hasher.finish()
+}
+
+// Digest of a set of lines, so it does not depend on their order
+pub fn digest(lines: &[String]) -> u64 {
+  let mut lines: Vec<&String> = lines.iter().collect();
+  lines.sort();
+  let mut hasher = Fnv1a::new();
+  for line in lines {
+    hasher.write(line.as_bytes());
+    hasher.write(b"\n");
+  }
+  hasher.finish()
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Reference values of 64 bit FNV-1a
+  #[test]
+  fn fnv1a_reference() {
+    assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+    assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+  }
+
+  #[test]
+  fn digest_ignores_order() {
+    let lines: Vec<String> = (0..50).map(|i| format!("1_{:03} | {} | [{}, {}] | -", i % 7, 24 * i, i, 2 * i)).collect();
+    // A fixed permutation, 7 is coprime to 50
+    let mut shuffled: Vec<String> = (0..lines.len()).map(|i| lines[(7 * i + 3) % lines.len()].clone()).collect();
+    assert_ne!(shuffled, lines);
+    assert_eq!(digest(&shuffled), digest(&lines));
+    shuffled.reverse();
+    assert_eq!(digest(&shuffled), digest(&lines));
+
+    // A changed count is a different set
+    let mut changed = lines.clone();
+    changed[10] = changed[10].replace("[10, 20]", "[10, 21]");
+    assert_ne!(digest(&changed), digest(&lines));
+  }
+}
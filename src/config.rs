@@ -0,0 +1,301 @@
+use std::{fs, thread};
+
+use crate::{FIELD_EXT_LUT, automorphisms, extension_ladder, orbits, validation, zeta};
+use crate::checksum::fnv1a;
+use crate::output::OutputFormat;
+
+
+//
+//
+// Command line configuration of a run
+// Everything that used to be a constant in main.rs and had to be recompiled for every (degree, field) pair
+//
+//
+
+
+pub const USAGE: &str = "Usage: affiene_variety --degree <d> --field <q> [options]
+
+Options:
+  --degree <d>       Homogeneous degree of the polynomials
+  --field <q>        Order of the field the polynomials are defined over (2, 3, 4, 5, 7, 8 or 9)
+  --max-ext <k>      Largest field extension k to check for singular points (default depends on the degree, required above degree 6)
+  --count-ext <k>    Give point counts up to F_q^k, beyond the genus they are derived from the L-polynomial instead of enumerated
+                     The Weil bound q^k + 1 + 2g q^(k/2) on the counts has to stay below 2^64
+  --euler            Test two partial derivatives in the points of the curve instead of three, the third vanishes with them
+                     by Euler's formula d F = x F_x + y F_y + z F_z
+  --threads <n>      Amount of worker threads (default the amount of available cores)
+  --chunk-size <n>   Amount of isomorphism classes a coordinator hands to a worker at once (default 1024)
+                     Local threads size their chunks from the measured time per class
+  --input <path>     Isomorphism class file (default input/<d>-<q>.txt, falling back to input.txt)
+  --output <path>    File the smooth representatives are written to (default ./output.txt)
+  --generate-orbits  Generate the isomorphism classes under PGL3 and write them to the input file first
+  --validate         Check that the input file covers every polynomial exactly once before counting
+  --verify-exact     Check the smoothness of every representative with Groebner bases and compare with the point search
+  --check-stabilizers
+                     Compute the automorphism group of every representative and check the class sizes in the input file
+  --format <f>       Format of the output file: text, csv, json or jsonl (default text)
+  --singular-output <path>
+                     Write every singular representative with the first extension it is singular over
+  --singular-points <path>
+                     Write every singular point of the singular representatives, with its degree, multiplicity and tangent cone
+  --journal <path>   File the finished chunks are written to while running (default <output>.journal), removed at the end
+  --resume           Skip the chunks in the journal of an interrupted run and merge their results
+  --fresh            Start over even though the journal of an interrupted run exists, which deletes it
+  --coordinator <address>
+                     Hand out the chunks over TCP to workers on address (like 0.0.0.0:7878) instead of counting locally
+  --worker <address> Count chunks for the coordinator on address, with --threads connections
+                     A worker needs the same degree, field, extensions and input file as the coordinator
+  --lookup-cache <dir>
+                     Keep the lookup tables in dir, they are read from there on later runs instead of computed
+  --memory-budget <MiB>
+                     Memory the lookup tables may take, an extension whose table does not fit generates its points in blocks
+  --quiet            Don't print progress of every chunk
+  --help             Print this message";
+
+// Fields for which a kernel is compiled in, see main::run
+pub const SUPPORTED_FIELDS: [usize; 7] = [2, 3, 4, 5, 7, 8, 9];
+// Largest amount of 64 bit words in a packed polynomial for which a kernel is compiled in
+pub const MAX_WORDS: usize = 4;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+  pub degree: usize,
+  pub field_order: usize,
+  pub max_field_ext: usize,
+  // Use Euler's formula to skip a derivative test, see Polynomial::has_singularity_point
+  pub euler: bool,
+  // Largest extension we want point counts for
+  pub count_ext: usize,
+  // Largest extension we actually enumerate points over, counts above it follow from the L-polynomial
+  pub enumerated_ext: usize,
+  // Amount of 64 bit words a packed polynomial needs
+  pub words: usize,
+  pub num_threads: usize,
+  pub chunk_size: usize,
+  pub input: String,
+  pub output: String,
+  pub format: OutputFormat,
+  pub singular_output: Option<String>,
+  pub singular_points: Option<String>,
+  // Generate the isomorphism classes ourselves instead of reading them from an existing file
+  pub generate_orbits: bool,
+  // Check the class sizes and representatives of the input file before counting
+  pub validate: bool,
+  // Compare the point search with the exact smoothness test
+  pub verify_exact: bool,
+  // Recompute every class size from the stabilizer of its representative
+  pub check_stabilizers: bool,
+  pub journal: String,
+  // Continue from the journal instead of starting over
+  pub resume: bool,
+  // Address to hand out chunks on, or of the coordinator to get them from
+  pub coordinator: Option<String>,
+  pub worker: Option<String>,
+  // Directory of the lookup table cache
+  pub lookup_cache: Option<String>,
+  // Bytes the lookup tables may take together, None for no limit
+  pub memory_budget: Option<usize>,
+  pub printing: bool,
+}
+
+impl Config {
+  pub fn from_args(args: impl Iterator<Item = String>) -> Result<Config, String> {
+    let mut degree = None;
+    let mut field_order = None;
+    let mut max_field_ext = None;
+    let mut count_ext = None;
+    let mut euler = false;
+    let mut num_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut chunk_size = 1024;
+    let mut input = None;
+    let mut output = "./output.txt".to_owned();
+    let mut format = OutputFormat::Text;
+    let mut singular_output = None;
+    let mut singular_points = None;
+    let mut generate_orbits = false;
+    let mut validate = false;
+    let mut verify_exact = false;
+    let mut check_stabilizers = false;
+    let mut journal = None;
+    let mut resume = false;
+    let mut fresh = false;
+    let mut coordinator = None;
+    let mut worker = None;
+    let mut lookup_cache = None;
+    let mut memory_budget = None;
+    let mut printing = true;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+      match arg.as_str() {
+        "--degree" => degree = Some(parse_number(&arg, args.next())?),
+        "--field" => field_order = Some(parse_number(&arg, args.next())?),
+        "--max-ext" => max_field_ext = Some(parse_number(&arg, args.next())?),
+        "--count-ext" => count_ext = Some(parse_number(&arg, args.next())?),
+        "--euler" => euler = true,
+        "--threads" => num_threads = parse_number(&arg, args.next())?,
+        "--chunk-size" => chunk_size = parse_number(&arg, args.next())?,
+        "--input" => input = Some(parse_value(&arg, args.next())?),
+        "--output" => output = parse_value(&arg, args.next())?,
+        "--format" => {
+          let value = parse_value(&arg, args.next())?;
+          format = OutputFormat::parse(&value).ok_or(format!("Unknown output format '{}', expected text, csv, json or jsonl", value))?;
+        },
+        "--singular-output" => singular_output = Some(parse_value(&arg, args.next())?),
+        "--singular-points" => singular_points = Some(parse_value(&arg, args.next())?),
+        "--generate-orbits" => generate_orbits = true,
+        "--validate" => validate = true,
+        "--verify-exact" => verify_exact = true,
+        "--check-stabilizers" => check_stabilizers = true,
+        "--journal" => journal = Some(parse_value(&arg, args.next())?),
+        "--resume" => resume = true,
+        "--fresh" => fresh = true,
+        "--coordinator" => coordinator = Some(parse_value(&arg, args.next())?),
+        "--worker" => worker = Some(parse_value(&arg, args.next())?),
+        "--lookup-cache" => lookup_cache = Some(parse_value(&arg, args.next())?),
+        "--memory-budget" => {
+          let mebibytes = parse_number(&arg, args.next())?;
+          memory_budget = Some(mebibytes.checked_mul(1 << 20).ok_or(format!("Memory budget of {} MiB does not fit in a usize of bytes", mebibytes))?);
+        },
+        "--quiet" => printing = false,
+        "--help" => return Err(USAGE.to_owned()),
+        _ => return Err(format!("Unknown argument '{}'", arg)),
+      }
+    }
+
+    let degree = degree.ok_or("Missing required argument --degree")?;
+    let field_order = field_order.ok_or("Missing required argument --field")?;
+
+    let field = match extension_ladder::field_info(field_order) {
+      Some(field) if SUPPORTED_FIELDS.contains(&field_order) => field,
+      _ => return Err(format!("Field order {} is not supported, expected one of {:?}", field_order, SUPPORTED_FIELDS)),
+    };
+    // Exponents are kept in a u8
+    if degree == 0 || degree > u8::MAX as usize {
+      return Err(format!("Degree has to be between 1 and {}", u8::MAX));
+    }
+    let bits = dplus2_choose_2(degree).and_then(|n| n.checked_mul(field.coeff_bits)).ok_or(format!("Degree {} has too many monomials", degree))?;
+    let words = bits.div_ceil(64);
+    if words > MAX_WORDS {
+      return Err(format!("Degree {} over F{} needs {} bits per polynomial, only {} are available", degree, field_order, bits, 64 * MAX_WORDS));
+    }
+
+    let max_field_ext = match max_field_ext {
+      Some(k) => k,
+      None => *FIELD_EXT_LUT.get(degree).ok_or(format!("No default maximal field extension known for degree {}, pass --max-ext", degree))?,
+    };
+    if max_field_ext == 0 || max_field_ext > field.max_field_ext {
+      return Err(format!("Maximal field extension over F{} has to be between 1 and {}", field_order, field.max_field_ext));
+    }
+
+    // Smoothness needs max_field_ext, predicting counts needs the first genus ones
+    let count_ext = count_ext.unwrap_or(max_field_ext);
+    // Every predicted count of a smooth curve is below the Weil bound, so it fits in a usize
+    if zeta::max_point_count(field_order as u64, zeta::genus(degree), count_ext).is_none_or(|n| n > usize::MAX as u128) {
+      return Err(format!("Point counts over F{}^k can reach q^k + 1 + 2g q^(k/2), which has to stay below 2^64, so --count-ext {} is out of range", field_order, count_ext));
+    }
+    let enumerated_ext = max_field_ext.max(count_ext.min(zeta::genus(degree)));
+    if enumerated_ext > field.max_field_ext {
+      return Err(format!("Point counts up to F{}^{} need enumerating up to extension {}, only {} is supported", field_order, count_ext, enumerated_ext, field.max_field_ext));
+    }
+
+    if num_threads == 0 || chunk_size == 0 {
+      return Err("Amount of threads and chunk size have to be at least 1".to_owned());
+    }
+
+    if generate_orbits {
+      orbits::check_generation(field_order, degree)?;
+    }
+
+    if validate {
+      validation::check_validation(field_order, degree)?;
+    }
+
+    if check_stabilizers {
+      automorphisms::check_stabilizers(field_order, degree)?;
+    }
+
+    if coordinator.is_some() && worker.is_some() {
+      return Err("A run is either a --coordinator or a --worker".to_owned());
+    }
+
+    let input = input.unwrap_or_else(|| {
+      let path = format!("input/{}-{}.txt", degree, field_order);
+      if generate_orbits || fs::metadata(&path).is_ok() {
+        path
+      } else {
+        println!("File not found, defaulting to 'input.txt'");
+        "input.txt".to_owned()
+      }
+    });
+
+    let journal = journal.unwrap_or_else(|| format!("{}.journal", output));
+    if resume && fresh {
+      return Err("A run either continues the journal with --resume or starts over with --fresh".to_owned());
+    }
+    // A journal is only left behind by a run that did not finish, starting over would throw its chunks away
+    if worker.is_none() && !resume && !fresh && fs::metadata(&journal).is_ok() {
+      return Err(format!("Journal {} of an interrupted run exists, continue it with --resume or start over with --fresh", journal));
+    }
+
+    Ok(Config { degree, field_order, max_field_ext, euler, count_ext, enumerated_ext, words, num_threads, chunk_size, input, output, format, singular_output, singular_points, generate_orbits, validate, verify_exact, check_stabilizers, journal, resume, coordinator, worker, lookup_cache, memory_budget, printing })
+  }
+
+  // Everything the results of a chunk depend on, the input by a hash of its contents and not by its path
+  // Workers have to agree on it with the coordinator, and a journal is only resumed by a run with the same one
+  pub fn run_identity(&self, input: &str, classes: usize) -> String {
+    format!("degree {} | field {} | extensions {} | input {:016x} | classes {}", self.degree, self.field_order, self.enumerated_ext, fnv1a(input.as_bytes()), classes)
+  }
+
+  pub fn pgl3_size(&self) -> f64 {
+    let q = self.field_order as f64;
+    q.powi(3) * (q.powi(3) - 1.) * (q.powi(2) - 1.)
+  }
+}
+
+
+// The amount of monomials of the degree, None when that overflows
+pub fn dplus2_choose_2(degree: usize) -> Option<usize> {
+  degree.checked_add(2)?.checked_mul(degree + 1).map(|n| n / 2)
+}
+
+fn parse_value(flag: &str, value: Option<String>) -> Result<String, String> {
+  value.ok_or(format!("Missing value for {}", flag))
+}
+
+fn parse_number(flag: &str, value: Option<String>) -> Result<usize, String> {
+  let value = parse_value(flag, value)?;
+  value.parse::<usize>().map_err(|_| format!("Expected a number for {}, got '{}'", flag, value))
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn from_args(args: &str) -> Result<Config, String> {
+    Config::from_args(format!("--degree 3 --field 2 --input unused --fresh {}", args).split_whitespace().map(str::to_owned))
+  }
+
+  #[test]
+  fn degree_out_of_range() {
+    let from_degree = |degree: &str| Config::from_args(format!("--degree {} --field 2 --max-ext 1 --input unused --fresh", degree).split_whitespace().map(str::to_owned));
+    assert!(from_degree("18446744073709551615").is_err());
+    assert!(from_degree("5000000000").is_err());
+    assert!(from_degree("256").is_err());
+    assert!(from_degree("0").is_err());
+    // 253 coefficients of 1 bit fit in 4 words, 276 do not
+    assert_eq!(from_degree("21").map(|c| c.words), Ok(4));
+    assert!(from_degree("22").is_err());
+    assert_eq!(dplus2_choose_2(usize::MAX), None);
+    assert_eq!(dplus2_choose_2(3), Some(10));
+  }
+
+  #[test]
+  fn memory_budget_in_mebibytes() {
+    assert_eq!(from_args("--memory-budget 3").unwrap().memory_budget, Some(3 << 20));
+    assert!(from_args(&format!("--memory-budget {}", usize::MAX >> 19)).is_err());
+    assert!(from_args("--memory-budget -1").is_err());
+  }
+}
@@ -0,0 +1,335 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{atomic::{AtomicBool, Ordering}, mpsc::Sender, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::algebraic_types::IsoPolynomial;
+use crate::extension_ladder::ExtensionLadder;
+use crate::journal::ChunkRecord;
+use crate::scheduler::ChunkResult;
+use crate::{is_smooth, CustomChunk};
+
+
+//
+//
+// Counting on several processes or machines, a coordinator hands out chunks over TCP to workers
+// Every worker reads the same input file and builds the same lookup tables, so only chunk ranges and results are sent
+// The protocol is line based, after "hello | <run>" and "ok" the coordinator sends "chunk <start> <end>"
+// and the worker answers with the chunk in the format of the journal, until the coordinator sends "done"
+// Workers send "alive" while counting, a worker that disconnects or is silent too long is dead and its chunk is handed out again
+// A result that does not fit the chunk or the extensions of the run gets "error" like a wrong hello, and the chunk is handed out again
+//
+//
+
+
+const HEARTBEAT: Duration = Duration::from_secs(5);
+const WORKER_TIMEOUT: Duration = Duration::from_secs(60);
+const POLL: Duration = Duration::from_millis(100);
+
+
+struct Queue {
+  chunks: Vec<CustomChunk>,
+  // Chunks that are handed out or waiting, a chunk of a dead worker can come back until this is 0
+  outstanding: usize,
+}
+
+fn next_chunk(queue: &Mutex<Queue>) -> Option<CustomChunk> {
+  loop {
+    {
+      let mut queue = queue.lock().unwrap();
+      if let Some(chunk) = queue.chunks.pop() {
+        return Some(chunk)
+      }
+      if queue.outstanding == 0 {
+        return None
+      }
+    }
+    thread::sleep(POLL);
+  }
+}
+
+fn read_line(reader: &mut impl BufRead) -> Result<String, String> {
+  let mut line = String::new();
+  match reader.read_line(&mut line) {
+    Ok(0) => Err("connection closed".to_owned()),
+    Ok(_) => Ok(line.trim_end().to_owned()),
+    Err(e) => Err(e.to_string()),
+  }
+}
+
+// A class has points for every extension before its first singular one, and for all of them if it is smooth
+fn receive(reader: &mut impl BufRead, chunk: CustomChunk, extensions: usize) -> Result<ChunkRecord, String> {
+  loop {
+    let line = read_line(reader)?;
+    if line == "alive" {
+      continue;
+    }
+    return match ChunkRecord::parse(&line) {
+      Some(record) if record.start == chunk.start && record.end() == chunk.end && record.matches_extensions(extensions) => Ok(record),
+      _ => Err(format!("invalid result '{}'", line)),
+    }
+  }
+}
+
+fn handle<const W: usize>(stream: TcpStream, identity: &str, extensions: usize, queue: &Mutex<Queue>, iso_polys: &[IsoPolynomial<W>], tx: &Sender<ChunkResult<W>>) -> Result<(), String> {
+  stream.set_read_timeout(Some(WORKER_TIMEOUT)).map_err(|e| e.to_string())?;
+  let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+  let mut writer = stream;
+
+  let hello = read_line(&mut reader)?;
+  if hello != format!("hello | {}", identity) {
+    let _ = writeln!(writer, "error | expected run '{}'", identity);
+    return Err(format!("worker runs '{}'", hello.trim_start_matches("hello | ")))
+  }
+  writeln!(writer, "ok").map_err(|e| e.to_string())?;
+
+  while let Some(chunk) = next_chunk(queue) {
+    let record = writeln!(writer, "chunk {} {}", chunk.start, chunk.end)
+      .map_err(|e| e.to_string())
+      .and_then(|_| receive(&mut reader, chunk, extensions));
+    match record {
+      Ok(record) => {
+        queue.lock().unwrap().outstanding -= 1;
+        let count = record.count.clone();
+        tx.send((chunk.start, (count, record.results(iso_polys)))).unwrap();
+      },
+      Err(e) => {
+        queue.lock().unwrap().chunks.push(chunk);
+        let _ = writeln!(writer, "error | {}", e);
+        return Err(format!("{}, classes {}..{} are handed out again", e, chunk.start, chunk.end))
+      },
+    }
+  }
+  writeln!(writer, "done").map_err(|e| e.to_string())
+}
+
+// The threads talking to the workers of a coordinator
+pub struct Coordinator {
+  handlers: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Coordinator {
+  // Once every class has arrived, waits until every connected worker has been sent "done"
+  pub fn finish(self) {
+    let handlers = std::mem::take(&mut *self.handlers.lock().unwrap());
+    for handler in handlers {
+      handler.join().unwrap();
+    }
+  }
+}
+
+// Hands out the chunks to every worker that connects, the results arrive on tx like those of local threads
+// Results have point counts for at most extensions field extensions, see receive
+pub fn serve<const W: usize>(address: &str, identity: String, extensions: usize, chunks: Vec<CustomChunk>, iso_polys: Arc<Vec<IsoPolynomial<W>>>, tx: Sender<ChunkResult<W>>) -> Coordinator {
+  let listener = TcpListener::bind(address).expect("Unable to listen on coordinator address");
+  println!("Coordinator listening on {}", address);
+  let queue = Arc::new(Mutex::new(Queue { outstanding: chunks.len(), chunks }));
+  let handlers = Arc::new(Mutex::new(vec![]));
+
+  let accepted = handlers.clone();
+  thread::spawn(move || {
+    for stream in listener.incoming().flatten() {
+      let (identity, queue, iso_polys, tx) = (identity.clone(), queue.clone(), iso_polys.clone(), tx.clone());
+      accepted.lock().unwrap().push(thread::spawn(move || {
+        let peer = stream.peer_addr().map_or("unknown".to_owned(), |a| a.to_string());
+        println!("Worker {} connected", peer);
+        match handle(stream, &identity, extensions, &queue, &iso_polys, &tx) {
+          Ok(()) => println!("Worker {} finished", peer),
+          Err(e) => println!("Worker {} lost: {}", peer, e),
+        }
+      }));
+    }
+  });
+  Coordinator { handlers }
+}
+
+
+fn connect(address: &str) -> Result<TcpStream, String> {
+  let start = Instant::now();
+  loop {
+    match TcpStream::connect(address) {
+      Ok(stream) => return Ok(stream),
+      Err(e) if start.elapsed() > WORKER_TIMEOUT => return Err(e.to_string()),
+      Err(_) => thread::sleep(Duration::from_secs(1)),
+    }
+  }
+}
+
+// One connection, so one worker as seen by the coordinator, returns the amount of chunks it counted
+fn work_connection<const W: usize>(address: &str, identity: &str, iso_polys: &[IsoPolynomial<W>], ladder: &ExtensionLadder<W>) -> Result<usize, String> {
+  let stream = connect(address)?;
+  let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+  let writer = Arc::new(Mutex::new(stream));
+
+  writeln!(writer.lock().unwrap(), "hello | {}", identity).map_err(|e| e.to_string())?;
+  let reply = read_line(&mut reader)?;
+  if reply != "ok" {
+    return Err(reply)
+  }
+
+  let stop = Arc::new(AtomicBool::new(false));
+  {
+    let (writer, stop) = (writer.clone(), stop.clone());
+    thread::spawn(move || {
+      while !stop.load(Ordering::Relaxed) && writeln!(writer.lock().unwrap(), "alive").is_ok() {
+        thread::sleep(HEARTBEAT);
+      }
+    });
+  }
+
+  let mut done = 0;
+  let result = loop {
+    let line = match read_line(&mut reader) {
+      Ok(line) => line,
+      Err(e) => break Err(e),
+    };
+    if line == "done" {
+      break Ok(done)
+    }
+    let range: Option<Vec<usize>> = line.strip_prefix("chunk ").and_then(|r| r.split(' ').map(|v| v.parse().ok()).collect());
+    let (start, end) = match range.as_deref() {
+      Some(&[start, end]) if start < end && end <= iso_polys.len() => (start, end),
+      _ => break Err(format!("unexpected message '{}'", line)),
+    };
+
+    let (count, results) = is_smooth(iso_polys, start, end, ladder);
+    if let Err(e) = writeln!(writer.lock().unwrap(), "{}", ChunkRecord::new(start, count, &results).line()) {
+      break Err(e.to_string())
+    }
+    done += 1;
+  };
+  stop.store(true, Ordering::Relaxed);
+  result
+}
+
+// The amount of chunks counted, or an error if a connection ended without "done"
+pub fn work<const W: usize>(address: &str, identity: &str, iso_polys: Arc<Vec<IsoPolynomial<W>>>, ladder: Arc<ExtensionLadder<W>>, connections: usize) -> Result<usize, String> {
+  let handles: Vec<_> = (0..connections).map(|_| {
+    let (address, identity, iso_polys, ladder) = (address.to_owned(), identity.to_owned(), iso_polys.clone(), ladder.clone());
+    thread::spawn(move || work_connection(&address, &identity, &iso_polys, &ladder))
+  }).collect();
+
+  let (mut done, mut failed) = (0, 0);
+  for handle in handles {
+    match handle.join().unwrap() {
+      Ok(chunks) => done += chunks,
+      Err(e) => {
+        println!("Connection to coordinator {} failed: {}", address, e);
+        failed += 1;
+      },
+    }
+  }
+  println!("Worker counted {} chunks", done);
+  if failed > 0 {
+    return Err(format!("{} of {} connections to coordinator {} failed", failed, connections, address))
+  }
+  Ok(done)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::mpsc;
+  use crate::config::Config;
+  use crate::field_extensions::{FieldFamily, F3};
+  use crate::orbits;
+  use crate::polynomials::Polynomial;
+
+  fn free_address() -> String {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string()
+  }
+
+  // Connects and says hello like a worker, returns the reply of the coordinator
+  fn hello(address: &str, identity: &str) -> (TcpStream, BufReader<TcpStream>, String) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    writeln!(stream, "hello | {}", identity).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let reply = read_line(&mut reader).unwrap();
+    (stream, reader, reply)
+  }
+
+  // Two workers with two connections each, and one more worker that disconnects while it has a chunk
+  #[test]
+  fn workers_on_localhost() {
+    let args = ["--degree", "3", "--field", "3", "--input", "unused", "--fresh"];
+    let config = Config::from_args(args.iter().map(|a| a.to_string())).unwrap();
+    let normal = Polynomial::<1>::generate_default_lut(3);
+    let (part_x, part_y, part_z) = Polynomial::<1>::generate_derative_luts(&normal, 3);
+    let iso_polys = Arc::new(orbits::generate::<<F3 as FieldFamily>::Base, 1>(&normal));
+    let ladder = Arc::new(ExtensionLadder::<1>::create::<F3>(&config, &normal, &part_x, &part_y, &part_z));
+    let identity = config.run_identity("input", iso_polys.len());
+    let address = free_address();
+
+    let classes = iso_polys.len();
+    let chunks: Vec<CustomChunk> = (0..classes).step_by(3).map(|start| CustomChunk { start, end: classes.min(start + 3) }).rev().collect();
+    let amount_of_chunks = chunks.len();
+    let (tx, rx) = mpsc::channel();
+    let coordinator = serve(&address, identity.clone(), config.enumerated_ext, chunks, iso_polys.clone(), tx);
+
+    let (stream, mut reader, reply) = hello(&address, &identity);
+    assert_eq!(reply, "ok");
+    assert!(read_line(&mut reader).unwrap().starts_with("chunk "));
+    drop((stream, reader));
+
+    let workers: Vec<_> = (0..2).map(|_| {
+      let (address, identity, iso_polys, ladder) = (address.clone(), identity.clone(), iso_polys.clone(), ladder.clone());
+      thread::spawn(move || work(&address, &identity, iso_polys, ladder, 2))
+    }).collect();
+
+    let mut results = vec![None; classes];
+    let mut left = classes;
+    while left > 0 {
+      let (start, (_, chunk)) = rx.recv().unwrap();
+      for (i, result) in chunk.into_iter().enumerate() {
+        assert!(results[start + i].replace(result.points_on_curve).is_none(), "class {} arrived twice", start + i);
+        left -= 1;
+      }
+    }
+    coordinator.finish();
+
+    // Every connection ends with "done", and the chunk of the lost worker was counted again
+    let counted: usize = workers.into_iter().map(|w| w.join().unwrap().expect("a connection failed")).sum();
+    assert_eq!(counted, amount_of_chunks);
+
+    let polys: Vec<Polynomial<1>> = iso_polys.iter().map(|iso| iso.representative).collect();
+    assert_eq!(results.into_iter().map(Option::unwrap).collect::<Vec<_>>(), ladder.points_on_curve(&polys));
+  }
+
+  #[test]
+  fn other_run_is_refused() {
+    let address = free_address();
+    let (tx, _rx) = mpsc::channel::<ChunkResult<1>>();
+    let coordinator = serve(&address, "run a".to_owned(), 1, vec![], Arc::new(vec![]), tx);
+    let (_stream, _reader, reply) = hello(&address, "run b");
+    assert!(reply.starts_with("error"));
+    coordinator.finish();
+  }
+
+  // A result with the wrong amount of extensions is refused, and its chunk goes to the next worker
+  #[test]
+  fn result_of_other_extensions_is_refused() {
+    let address = free_address();
+    let (tx, rx) = mpsc::channel::<ChunkResult<1>>();
+    let iso_polys = Arc::new(vec![IsoPolynomial { representative: Polynomial::new([0]), size: 1 }; 2]);
+    let coordinator = serve(&address, "run".to_owned(), 2, vec![CustomChunk { start: 0, end: 2 }], iso_polys, tx);
+
+    for line in ["0 | 1,0,0 | 1,2;1", "0 | 1,0 | 1,2,3;1"] {
+      let (mut stream, mut reader, reply) = hello(&address, "run");
+      assert_eq!(reply, "ok");
+      assert_eq!(read_line(&mut reader).unwrap(), "chunk 0 2");
+      writeln!(stream, "{}", line).unwrap();
+      assert!(read_line(&mut reader).unwrap().starts_with("error"), "{}", line);
+    }
+
+    let (mut stream, mut reader, _) = hello(&address, "run");
+    assert_eq!(read_line(&mut reader).unwrap(), "chunk 0 2");
+    writeln!(stream, "0 | 1,0 | 1,2;1").unwrap();
+    assert_eq!(read_line(&mut reader).unwrap(), "done");
+    let (start, (count, results)) = rx.recv().unwrap();
+    assert_eq!((start, count), (0, vec![1, 0]));
+    assert_eq!(results.into_iter().map(|r| r.points_on_curve).collect::<Vec<_>>(), vec![vec![1, 2], vec![1]]);
+    coordinator.finish();
+  }
+}
@@ -0,0 +1,303 @@
+use crate::algebraic_types::Lookup;
+use crate::config::Config;
+use crate::field_extensions::{FieldFamily, FieldTraits, F2_i, F3_i, F5_i, F7_i, F2, F3, F4, F5, F7, F8, F9};
+use crate::polynomials::{Polynomial, Term};
+use crate::singular_points::SingularPoint;
+
+
+//
+//
+// The ladder of lookup tables over F_q^1 up to F_q^max_field_ext
+// Every extension has its own field type, so every rung is a boxed lookup table behind a trait object
+// Raising the maximal extension is just a different max_field_ext, the macro below generates every rung
+//
+//
+
+
+// A lookup table for a single field extension, with the field type erased
+pub trait ExtensionLookup<const W: usize>: Send + Sync {
+  fn has_singularity_batch(&self, polys: &[Polynomial<W>], euler: bool) -> Vec<Option<usize>>;
+  fn singular_points(&self, poly: Polynomial<W>, normal: &[Term]) -> Vec<SingularPoint>;
+  fn memory(&self) -> usize;
+  fn streams(&self) -> bool;
+}
+
+impl<F: FieldTraits + Send + Sync, const W: usize> ExtensionLookup<W> for Lookup<F> {
+  fn has_singularity_batch(&self, polys: &[Polynomial<W>], euler: bool) -> Vec<Option<usize>> {
+    Polynomial::has_singularity_batch(polys, self, euler)
+  }
+
+  fn singular_points(&self, poly: Polynomial<W>, normal: &[Term]) -> Vec<SingularPoint> {
+    Lookup::singular_points(self, poly, normal)
+  }
+
+  fn memory(&self) -> usize {
+    Lookup::memory(self)
+  }
+
+  fn streams(&self) -> bool {
+    self.table.is_none()
+  }
+}
+
+
+// A field family for which lookup tables can be made at every extension degree up to MAX_FIELD_EXT
+pub trait LadderFamily: FieldFamily {
+  const MAX_FIELD_EXT: usize;
+
+  fn create_lookup<const W: usize>(k: usize, normal: &[Term], part_x: &[Term], part_y: &[Term], part_z: &[Term], cache: Option<&str>, budget: Option<usize>) -> Option<Box<dyn ExtensionLookup<W>>>;
+}
+
+// For F_q with q = p^r the k-th rung is the field of degree r*k over F_p, so $field<{r * k}>
+macro_rules! impl_ladder_family {
+  ($family:ty, $field:ident, $r:literal, $max:literal, [$($k:literal),*]) => {
+    impl LadderFamily for $family {
+      const MAX_FIELD_EXT: usize = $max;
+
+      fn create_lookup<const W: usize>(k: usize, normal: &[Term], part_x: &[Term], part_y: &[Term], part_z: &[Term], cache: Option<&str>, budget: Option<usize>) -> Option<Box<dyn ExtensionLookup<W>>> {
+        match k {
+          $($k => Some(Box::new(Lookup::<$field<{$r * $k}>>::create::<<$family as FieldFamily>::Base>(normal, part_x, part_y, part_z, cache, budget))),)*
+          _ => None,
+        }
+      }
+    }
+  };
+}
+
+// Bounded by the bits of an element and the irreducible polynomial tables
+impl_ladder_family!(F2, F2_i, 1, 16, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+impl_ladder_family!(F3, F3_i, 1, 10, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+impl_ladder_family!(F4, F2_i, 2, 8, [1, 2, 3, 4, 5, 6, 7, 8]);
+impl_ladder_family!(F5, F5_i, 1, 8, [1, 2, 3, 4, 5, 6, 7, 8]);
+impl_ladder_family!(F7, F7_i, 1, 8, [1, 2, 3, 4, 5, 6, 7, 8]);
+impl_ladder_family!(F8, F2_i, 3, 5, [1, 2, 3, 4, 5]);
+impl_ladder_family!(F9, F3_i, 2, 5, [1, 2, 3, 4, 5]);
+
+
+// Everything the configuration needs to know about a field before a kernel is picked
+pub struct FieldInfo {
+  pub max_field_ext: usize,
+  pub coeff_bits: usize,
+}
+
+impl FieldInfo {
+  fn of<B: LadderFamily>() -> FieldInfo {
+    FieldInfo { max_field_ext: B::MAX_FIELD_EXT, coeff_bits: B::Base::PACKED_BITS }
+  }
+}
+
+pub fn field_info(field_order: usize) -> Option<FieldInfo> {
+  match field_order {
+    2 => Some(FieldInfo::of::<F2>()),
+    3 => Some(FieldInfo::of::<F3>()),
+    4 => Some(FieldInfo::of::<F4>()),
+    5 => Some(FieldInfo::of::<F5>()),
+    7 => Some(FieldInfo::of::<F7>()),
+    8 => Some(FieldInfo::of::<F8>()),
+    9 => Some(FieldInfo::of::<F9>()),
+    _ => None,
+  }
+}
+
+
+pub struct ExtensionLadder<const W: usize> {
+  lookups: Vec<Box<dyn ExtensionLookup<W>>>,
+  // Test two partial derivatives on the curve instead of three, see Polynomial::has_singularity_point
+  euler: bool,
+}
+
+impl<const W: usize> ExtensionLadder<W> {
+  // A memory budget is shared by the rungs in order, a rung whose table does not fit in what is left streams its points
+  pub fn create<B: LadderFamily>(config: &Config, normal: &[Term], part_x: &[Term], part_y: &[Term], part_z: &[Term]) -> ExtensionLadder<W> {
+    let mut budget = config.memory_budget;
+    let lookups = (1..=config.enumerated_ext)
+      .map(|k| {
+        let lookup = B::create_lookup(k, normal, part_x, part_y, part_z, config.lookup_cache.as_deref(), budget).expect("Field extension not supported for this field");
+        budget = budget.map(|b| b.saturating_sub(lookup.memory()));
+        lookup
+      })
+      .collect();
+    ExtensionLadder { lookups, euler: config.euler }
+  }
+
+  pub fn max_field_ext(&self) -> usize {
+    self.lookups.len()
+  }
+
+  // Whether some extension generates its points for every batch of polynomials
+  pub fn streams(&self) -> bool {
+    self.lookups.iter().any(|lookup| lookup.streams())
+  }
+
+  // Walks up the extensions and drops a polynomial at the first one with a singular point
+  // Returns per polynomial the amount of points on the curve for every extension before that, so it is smooth iff all are there
+  // The lookups give closed points c_j of exact degree j, the points over F_q^k are then N_k = sum over j | k of j c_j
+  // All polynomials go through a lookup together, so a streamed lookup generates its points once per batch
+  pub fn points_on_curve(&self, polys: &[Polynomial<W>]) -> Vec<Vec<usize>> {
+    let mut closed_points = vec![Vec::with_capacity(self.lookups.len()); polys.len()];
+    // Polynomials without a singular point so far
+    let mut alive: Vec<usize> = (0..polys.len()).collect();
+    for lookup in &self.lookups {
+      if alive.is_empty() {
+        break;
+      }
+      let batch: Vec<Polynomial<W>> = alive.iter().map(|&i| polys[i]).collect();
+      let results = lookup.has_singularity_batch(&batch, self.euler);
+      alive = alive.into_iter().zip(results).filter_map(|(i, points)| {
+        closed_points[i].push(points?);
+        Some(i)
+      }).collect();
+    }
+    closed_points.into_iter().map(|closed_points| {
+      (1..=closed_points.len())
+        .map(|k| (1..=k).filter(|j| k.is_multiple_of(*j)).map(|j| j * closed_points[j - 1]).sum())
+        .collect()
+    }).collect()
+  }
+
+  // All singular closed points over every extension, which does not stop at the first singular one
+  pub fn singular_points(&self, poly: Polynomial<W>, normal: &[Term]) -> Vec<SingularPoint> {
+    self.lookups.iter().flat_map(|lookup| lookup.singular_points(poly, normal)).collect()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::orbits;
+  use crate::algebraic_types::BaseEmbedding;
+  use crate::polynomials::tests::{polynomial, EVALUATIONS};
+
+  // Smoothness by the point search against the Groebner basis test, for every class of the degree over F_q
+  fn check_against_exact<B: LadderFamily, const W: usize>(degree: usize, field_order: usize) {
+    let normal = Polynomial::<W>::generate_default_lut(degree);
+    let (part_x, part_y, part_z) = Polynomial::<W>::generate_derative_luts(&normal, B::Base::CHARACTERISTIC);
+    let classes = orbits::generate::<B::Base, W>(&normal);
+    let polys: Vec<Polynomial<W>> = classes.iter().map(|c| c.representative).collect();
+
+    // Euler's formula holds whether or not p divides d, so --euler gives the same counts with fewer evaluations
+    let mut runs = vec![];
+    for euler in [false, true] {
+      let mut args = vec!["--degree".to_owned(), degree.to_string(), "--field".to_owned(), field_order.to_string(), "--input".to_owned(), "unused".to_owned(), "--fresh".to_owned()];
+      if euler {
+        args.push("--euler".to_owned());
+      }
+      let config = Config::from_args(args.into_iter()).unwrap();
+      let ladder = ExtensionLadder::<W>::create::<B>(&config, &normal, &part_x, &part_y, &part_z);
+
+      let evaluations = EVALUATIONS.with(|e| e.get());
+      let points_on_curve = ladder.points_on_curve(&polys);
+      runs.push((EVALUATIONS.with(|e| e.get()) - evaluations, points_on_curve.clone()));
+
+      let mut smooth = 0;
+      for (poly, points_on_curve) in polys.iter().zip(points_on_curve) {
+        let exact = poly.is_smooth_exact::<B::Base>(&normal).is_smooth();
+        assert_eq!(points_on_curve.len() == ladder.max_field_ext(), exact, "{} (euler {})", poly.str::<B::Base>(&normal), euler);
+        smooth += exact as usize;
+      }
+      assert!(smooth > 0 && smooth < polys.len());
+    }
+    let (without, with) = (&runs[0], &runs[1]);
+    assert_eq!(without.1, with.1);
+    assert!(with.0 < without.0, "--euler evaluated {} times, without {}", with.0, without.0);
+  }
+
+  // The characteristic divides the degree, so F does not follow from the partial derivatives
+  #[test]
+  fn cubics_over_f3() {
+    check_against_exact::<F3, 1>(3, 3);
+  }
+
+  #[test]
+  fn quartics_over_f2() {
+    check_against_exact::<F2, 1>(4, 2);
+  }
+
+  #[test]
+  fn cubics_over_f2() {
+    check_against_exact::<F2, 1>(3, 2);
+  }
+
+  // Coefficients outside the prime field go through the embedding of F4 in every rung
+  #[test]
+  fn cubics_over_f4() {
+    check_against_exact::<F4, 1>(3, 4);
+  }
+
+  // Points of the curve over F by trying every point of P^2(F), with the coefficients of poly in B
+  fn brute_force_count<B: FieldTraits, F: FieldTraits, const W: usize>(poly: Polynomial<W>, normal: &[Term]) -> usize {
+    let embedding = BaseEmbedding::<F>::new::<B>();
+    let coefficients: Vec<F> = (0..normal.len()).map(|i| embedding.embed(poly.coefficient::<B>(i))).collect();
+    F::iterate_over_points()
+      .filter(|&(x, y, z)| normal.iter().zip(&coefficients).fold(F::ZERO, |acc, (t, &c)| acc + c * t.evaluate(x, y, z)) == F::ZERO)
+      .count()
+  }
+
+  fn ladder<B: LadderFamily, const W: usize>(args: &str, normal: &[Term]) -> ExtensionLadder<W> {
+    let config = Config::from_args(format!("{} --input unused --fresh", args).split_whitespace().map(str::to_owned)).unwrap();
+    let (part_x, part_y, part_z) = Polynomial::<W>::generate_derative_luts(normal, B::Base::CHARACTERISTIC);
+    ExtensionLadder::<W>::create::<B>(&config, normal, &part_x, &part_y, &part_z)
+  }
+
+  // x^3 + y^3 + z^3 is the Hermitian curve over F4, with 4 + 1 + 2 * 2 points and Frobenius eigenvalues -2, -2
+  // x^3 + y^3 + w z^3 has the generator w of F4 as a coefficient, so every rung needs its image under the embedding
+  #[test]
+  fn point_counts_over_f4() {
+    let normal = Polynomial::<1>::generate_default_lut(3);
+    let ladder = ladder::<F4, 1>("--degree 3 --field 4", &normal);
+    for (terms, expected) in [([(3, 0, 0, 1), (0, 3, 0, 1), (0, 0, 3, 1)], Some(vec![9, 9, 81])), ([(3, 0, 0, 1), (0, 3, 0, 1), (0, 0, 3, 2)], None)] {
+      let (poly, _) = polynomial::<F2_i<2>>(3, &terms);
+      let poly = Polynomial::<1>::new([poly.bits[0]]);
+      assert!(poly.is_smooth_exact::<F2_i<2>>(&normal).is_smooth());
+      let counts = ladder.points_on_curve(&[poly]).remove(0);
+      let brute_force = vec![
+        brute_force_count::<F2_i<2>, F2_i<2>, 1>(poly, &normal),
+        brute_force_count::<F2_i<2>, F2_i<4>, 1>(poly, &normal),
+        brute_force_count::<F2_i<2>, F2_i<6>, 1>(poly, &normal),
+      ];
+      assert_eq!(counts, brute_force);
+      if let Some(expected) = expected {
+        assert_eq!(counts, expected);
+      }
+    }
+  }
+
+  // Degree 7 over F3 takes 36 coefficients of 2 bits, so two words, and x^7 is the last monomial in the second word
+  // Without x^7 the curve would be y^7 + z^7, seven lines through the singular point (1:0:0)
+  #[test]
+  fn polynomials_of_two_words() {
+    let normal = Polynomial::<2>::generate_default_lut(7);
+    let ladder = ladder::<F3, 2>("--degree 7 --field 3 --max-ext 2", &normal);
+    let config = Config::from_args("--degree 7 --field 3 --max-ext 2 --input unused --fresh".split_whitespace().map(str::to_owned)).unwrap();
+    assert_eq!(config.words, 2);
+    assert_eq!(normal.iter().position(|t| t.x_deg == 7), Some(35));
+
+    for terms in [vec![(7, 0, 0, 1), (0, 7, 0, 1), (0, 0, 7, 1)], vec![(7, 0, 0, 2), (6, 1, 0, 1), (0, 7, 0, 1), (0, 0, 7, 1)], vec![(0, 7, 0, 1), (0, 0, 7, 1)]] {
+      let (poly, _) = polynomial::<F3_i<1>>(7, &terms);
+      let poly = Polynomial::<2>::new([poly.bits[0], poly.bits[1]]);
+      assert_eq!(poly.bits[1] != 0, terms[0].0 >= 5);
+      let counts = ladder.points_on_curve(&[poly]).remove(0);
+      let smooth = poly.is_smooth_exact::<F3_i<1>>(&normal).is_smooth();
+      assert_eq!(counts.len() == 2, smooth, "{}", poly.str::<F3_i<1>>(&normal));
+      assert_eq!(smooth, terms[0].0 == 7);
+      let brute_force = [brute_force_count::<F3_i<1>, F3_i<1>, 2>(poly, &normal), brute_force_count::<F3_i<1>, F3_i<2>, 2>(poly, &normal)];
+      assert_eq!(counts, brute_force[..counts.len()]);
+    }
+  }
+
+  // y^2 z = x^3 + x z^2 - c z^3 over F9 for c = 0 and c = w with w^2 = -1, against the exact test and a count of all points over F9 and F81
+  #[test]
+  fn point_counts_over_f9() {
+    let normal = Polynomial::<1>::generate_default_lut(3);
+    let ladder = ladder::<F9, 1>("--degree 3 --field 9 --max-ext 2", &normal);
+    // w is constant 3 = 0 + 1 * 3
+    for c in [0, 3] {
+      let (poly, _) = polynomial::<F3_i<2>>(3, &[(0, 2, 1, 1), (3, 0, 0, 2), (1, 0, 2, 2), (0, 0, 3, c)]);
+      let poly = Polynomial::<1>::new([poly.bits[0]]);
+      let counts = ladder.points_on_curve(&[poly]).remove(0);
+      assert!(poly.is_smooth_exact::<F3_i<2>>(&normal).is_smooth());
+      assert_eq!(counts, vec![brute_force_count::<F3_i<2>, F3_i<2>, 1>(poly, &normal), brute_force_count::<F3_i<2>, F3_i<4>, 1>(poly, &normal)]);
+    }
+  }
+}
@@ -3,7 +3,7 @@ use std::{ops::{Add, Mul, AddAssign, MulAssign}, num::Wrapping};
 
 //
 //
-// Implementation of field extensions for field 2 and 3 up to a certain degree
+// Implementation of field extensions for field 2, 3, 5 and 7 up to a certain degree
 // Most notably, internal add and internal mul are interesting functions (Multiplying and adding field extended fields)
 // Lots of functions are just boilerplate functions to make it easier to use in other parts of the pogram
 //
@@ -11,13 +11,21 @@ use std::{ops::{Add, Mul, AddAssign, MulAssign}, num::Wrapping};
 
 
 
-pub trait FieldTraits: Sized + MulAssign + Copy {
+pub trait FieldTraits: Sized + Add<Output = Self> + Mul<Output = Self> + AddAssign + MulAssign + Copy + PartialEq {
   
   const ZERO: Self;
   const ONE: Self;
   const MAX: Self;
-  // fn zero() -> Self;
-  // fn max() -> Self;
+  // Characteristic of the field, so the prime field the coefficients of a polynomial live in
+  const CHARACTERISTIC: usize;
+  // Degree of the field as an extension of its prime field
+  const DEGREE: usize;
+  // Amount of bits a single prime field coefficient of an element takes up
+  const COEFF_BITS: usize;
+  // Amount of bits a whole element takes up as a coefficient of a packed polynomial
+  // Rounded up to a power of two, so a coefficient never straddles two words
+  const PACKED_BITS: usize = (Self::DEGREE * Self::COEFF_BITS).next_power_of_two();
+
   fn mul_ntimes(self, n: u8) -> Self {
     let mut res = Self::ONE;
     for _ in 0..n {
@@ -28,19 +36,145 @@ pub trait FieldTraits: Sized + MulAssign + Copy {
 
   fn next(self) -> Option<Self>;
 
+  fn to_bits(self) -> u64;
+  fn from_bits(bits: u64) -> Self;
+
+  // The i-th coefficient of the element, so the coefficient of x^i in its polynomial basis
+  fn digit(self, i: usize) -> u64 {
+    (self.to_bits() >> (Self::COEFF_BITS * i)) & ((1 << Self::COEFF_BITS) - 1)
+  }
+
+  // Multiplication by an element of the prime field, as repeated addition
+  fn scale(self, constant: u64) -> Self {
+    let mut res = Self::ZERO;
+    for _ in 0..constant {
+      res += self;
+    }
+    res
+  }
+
+  // Additive inverse, adding an element p times gives zero
+  fn neg(self) -> Self {
+    self.scale(Self::CHARACTERISTIC as u64 - 1)
+  }
+
+  // Square and multiply, so large exponents like the order of the field stay cheap
+  fn pow(self, mut n: u64) -> Self {
+    let mut res = Self::ONE;
+    let mut base = self;
+    while n > 0 {
+      if n & 1 == 1 {
+        res *= base;
+      }
+      base *= base;
+      n >>= 1;
+    }
+    res
+  }
+
+  // Multiplicative inverse through a^(q-2), zero stays zero
+  fn inverse(self) -> Self {
+    if self == Self::ZERO {
+      return Self::ZERO
+    }
+    self.pow((Self::CHARACTERISTIC as u64).pow(Self::DEGREE as u32) - 2)
+  }
+
+  // x^DEGREE written in the basis 1, x, ..., x^(DEGREE-1), this determines the irreducible polynomial of the field
+  fn reduced_modulus() -> u64 {
+    if Self::DEGREE == 1 {
+      return 0
+    }
+    Self::from_bits(1 << Self::COEFF_BITS).pow(Self::DEGREE as u64).to_bits()
+  }
+
   fn iterate_over_points() -> ProjectivePointIterator<Self> {
     ProjectivePointIterator::new()
   }
+
+  // One point of every closed point of degree k over F_q, where the field itself is F_q^k
+  fn iterate_over_closed_points(q: u64, k: usize) -> ClosedPointIterator<Self> {
+    ClosedPointIterator { points: Self::iterate_over_points(), q, k }
+  }
+}
+
+
+// The field F_q the coefficients of the polynomials live in, its extensions F_q^k are listed in extension_ladder
+// For q = p^r the extension F_q^k is the field of degree r*k over F_p
+pub trait FieldFamily: 'static {
+  type Base: FieldTraits + Send + Sync + 'static;
+}
+
+pub struct F2;
+impl FieldFamily for F2 {
+  type Base = F2_i<1>;
+}
+
+pub struct F3;
+impl FieldFamily for F3 {
+  type Base = F3_i<1>;
+}
+
+pub struct F4;
+impl FieldFamily for F4 {
+  type Base = F2_i<2>;
+}
+
+pub struct F5;
+impl FieldFamily for F5 {
+  type Base = F5_i<1>;
+}
+
+pub struct F7;
+impl FieldFamily for F7 {
+  type Base = F7_i<1>;
+}
+
+pub struct F8;
+impl FieldFamily for F8 {
+  type Base = F2_i<3>;
+}
+
+pub struct F9;
+impl FieldFamily for F9 {
+  type Base = F3_i<2>;
+}
+
+
+// The images of 1, w, ..., w^(r-1) in F, where w generates the polynomial basis of B = F_p^r
+// Every coefficient of a polynomial over B becomes an element of F by writing it in this basis
+pub fn embed_basis<B: FieldTraits, F: FieldTraits>() -> Vec<F> {
+  const { assert!(B::CHARACTERISTIC == F::CHARACTERISTIC && F::DEGREE.is_multiple_of(B::DEGREE), "B is not a subfield of F") };
+  if B::DEGREE == 1 {
+    return vec![F::ONE]
+  }
+
+  // w^r = c_0 + c_1 w + ... + c_(r-1) w^(r-1), so its image has to satisfy the same relation
+  let w = B::from_bits(1 << B::COEFF_BITS);
+  let relation = w.mul_ntimes(B::DEGREE as u8);
+  let satisfies = |alpha: F| {
+    let rhs = (0..B::DEGREE).fold(F::ZERO, |acc, j| acc + alpha.mul_ntimes(j as u8).scale(relation.digit(j)));
+    alpha.mul_ntimes(B::DEGREE as u8) == rhs
+  };
+
+  // Any root works, the others give the Frobenius conjugates of the polynomial, which have the same points and singularities
+  let mut alpha = F::ZERO;
+  while !satisfies(alpha) {
+    alpha = alpha.next().expect("F contains no root of the minimal polynomial of B");
+  }
+  (0..B::DEGREE).map(|j| alpha.mul_ntimes(j as u8)).collect()
 }
 
 
+#[derive(Clone)]
 enum ProjectivePointPhase {
-  START,
-  Z_NULL,
-  Z_ONE,
-  FINISHED,
+  Start,
+  ZNull,
+  ZOne,
+  Finished,
 }
 
+#[derive(Clone)]
 pub struct ProjectivePointIterator<T: FieldTraits> {
   phase: ProjectivePointPhase,
   x: T,
@@ -50,7 +184,7 @@ pub struct ProjectivePointIterator<T: FieldTraits> {
 impl<T: FieldTraits> ProjectivePointIterator<T> { 
   pub fn new() -> ProjectivePointIterator<T>{
     ProjectivePointIterator {
-        phase: ProjectivePointPhase::START,
+        phase: ProjectivePointPhase::Start,
         x: T::ZERO,
         y: T::ZERO,
     }
@@ -65,8 +199,10 @@ pub struct F2_i<const N: u8> {
 impl<const N: u8> FieldTraits for F2_i<N> {
   const ZERO: Self = F2_i { element: 0};
   const ONE: Self = F2_i { element: 1};
-  const MAX: Self =  F2_i {element: ((1<<N) - 1)};
-  
+  const MAX: Self =  F2_i {element: ((1u32 << N) - 1) as u16};
+  const CHARACTERISTIC: usize = 2;
+  const DEGREE: usize = N as usize;
+  const COEFF_BITS: usize = 1;
 
   fn next(self) -> Option<F2_i<N>> {
     if self == F2_i::MAX {
@@ -76,6 +212,14 @@ impl<const N: u8> FieldTraits for F2_i<N> {
     }
   }
 
+  fn to_bits(self) -> u64 {
+    self.element as u64
+  }
+
+  fn from_bits(bits: u64) -> Self {
+    F2_i { element: bits as u16 }
+  }
+
 }
 
 impl<const N: u8> F2_i<N> {
@@ -138,6 +282,7 @@ impl<const N: u8> F2_i<N> {
 impl<const N: u8> Add for F2_i<N> {
   type Output = Self;
   
+  #[allow(clippy::suspicious_arithmetic_impl)]
   fn add(self, rhs: Self) -> Self::Output {
     F2_i {element: self.element ^ rhs.element}
   }
@@ -152,8 +297,9 @@ impl<const N: u8> Mul for F2_i<N> {
 }
 
 impl<const N: u8> AddAssign for F2_i<N> {
+  #[allow(clippy::suspicious_op_assign_impl)]
   fn add_assign(&mut self, rhs: Self) {
-    self.element = self.element ^ rhs.element;
+    self.element ^= rhs.element;
   }
 }
 
@@ -165,33 +311,45 @@ impl<const N: u8> MulAssign for F2_i<N> {
 
 
 // A polynomial over the field over 3 elements, represented in bits
-// Each coefficient is represented by 2 bits, so the can be at most 16
+// Each coefficient is represented by 2 bits, so the can be at most 10
 #[allow(non_camel_case_types)]
 #[derive(Debug,Clone,Copy,PartialEq)]
 pub struct F3_i<const N: u8> {
-  pub element: u16,
+  pub element: u32,
 }
 
 impl<const N: u8> FieldTraits for F3_i<N> {
     const ZERO: F3_i<N> = F3_i {element: 0b0 };
     const ONE: F3_i<N> = F3_i {element: 0b1 };
-    const MAX: F3_i<N> = F3_i {element: 0xAAAA & !((!0)<< (2*N))} ; // DON'T 
+    const MAX: F3_i<N> = F3_i {element: (0xAAAAAAAA & !((!0u64) << (2*N))) as u32} ; // DON'T 
+    const CHARACTERISTIC: usize = 3;
+    const DEGREE: usize = N as usize;
+    const COEFF_BITS: usize = 2;
 
     fn next(self) -> Option<Self> {
       if self == Self::MAX {
         None 
       } else {
-        let t = ((self.element ^ 0xAAAA) | 0x5555) >> 1;
-        let el = (Wrapping(self.element) - Wrapping(t)).0 & t;
-        Some(F3_i {element: el as u16})
+        let element = self.element;
+        let t = ((element ^ 0xAAAAAAAA) | 0x55555555) >> 1;
+        let el = (Wrapping(element) - Wrapping(t)).0 & t;
+        Some(F3_i {element: el})
       }
     }
+
+    fn to_bits(self) -> u64 {
+      self.element as u64
+    }
+
+    fn from_bits(bits: u64) -> Self {
+      F3_i { element: bits as u32 }
+    }
 }
 
 impl<const N: u8> F3_i<N> {
   #[allow(dead_code)]
-  pub fn new(element: u16) -> F3_i<N> {
-    F3_i { element: element }
+  pub fn new(element: u32) -> F3_i<N> {
+    F3_i { element }
   }
   
   // print each coefficient as a number
@@ -222,8 +380,8 @@ impl<const N: u8> F3_i<N> {
     for i in 0..N {
       let factor = (lhs >> (2*i)) & 0b11;
       match factor {
-          2 => {result = F3_i::<N>::internal_add_fast(result, rhs << 2*i); result = F3_i::<N>::internal_add_fast(result, rhs << 2*i);}
-          1 => {result = F3_i::<N>::internal_add_fast(result, rhs << 2*i);}
+          2 => {result = F3_i::<N>::internal_add_fast(result, rhs << (2*i)); result = F3_i::<N>::internal_add_fast(result, rhs << (2*i));}
+          1 => {result = F3_i::<N>::internal_add_fast(result, rhs << (2*i));}
           _ => {}
       }
     }
@@ -232,23 +390,24 @@ impl<const N: u8> F3_i<N> {
 
   // We first convert the polynomials to vectors with coefficients in Z/3Z
   // Then we multiply them and reduce the result
-  fn internal_mul(a: u64, b: u64) -> u16 {
+  fn internal_mul(a: u64, b: u64) -> u32 {
     // Irred polys with lowest lexographical ordering according to:
     // Handbook of finite fields (Page 36, Table 2.2.3)
     // http://archive.ymsc.tsinghua.edu.cn/pacm_download/672/12637-dingjt-p2.pdf 
-    const IRRED_POLY: [u64; 7] = [0b0000, 0b0001, 0b0010, 0b0110,0b1001, 0b0110, 0b1001];
+    // 7 up to 10 are not in the table, these are the lexographically first ones in the same ordering
+    const IRRED_POLY: [u64; 11] = [0b0000, 0b0001, 0b0010, 0b0110,0b1001, 0b0110, 0b1001, 0b100001, 0b100001, 0b1100010, 0b10010];
 
-    let bitmask: u64 = !((!0) << 2*N);
+    let bitmask: u64 = !((!0) << (2*N));
     let irred = IRRED_POLY[N as usize];
 
     let mut result = F3_i::<N>::clmul(a, b); 
-    while (result >> N*2) > 0 {
+    while (result >> (N*2)) > 0 {
       let lsb = result & bitmask;
-      let msb = result >> 2*N;
+      let msb = result >> (2*N);
       result = F3_i::<N>::internal_add_fast(lsb, F3_i::<N>::clmul(msb, irred));
     }
 
-    result as u16
+    result as u32
   }
 
 }
@@ -257,7 +416,7 @@ impl<const N: u8> Add for F3_i<N> {
   type Output = Self;
   
   fn add(self, rhs: Self) -> Self::Output {
-    F3_i {element: F3_i::<N>::internal_add_fast(self.element as u64, rhs.element as u64) as u16}
+    F3_i {element: F3_i::<N>::internal_add_fast(self.element as u64, rhs.element as u64) as u32}
   }
 }
 
@@ -271,7 +430,7 @@ impl<const N: u8> Mul for F3_i<N> {
 
 impl<const N: u8> AddAssign for F3_i<N> {
   fn add_assign(&mut self, rhs: Self) {
-    self.element = F3_i::<N>::internal_add_fast(self.element as u64, rhs.element as u64) as u16;
+    self.element = F3_i::<N>::internal_add_fast(self.element as u64, rhs.element as u64) as u32;
   }
 }
 
@@ -283,24 +442,211 @@ impl<const N: u8> MulAssign for F3_i<N> {
 
 
 
+
+// A polynomial over the field over P elements (P = 5 or 7), represented in bits
+// Each coefficient is represented by 4 bits, so the can be at most 8
+// The coefficients never exceed 2P - 2 < 16 during addition, so all nibbles can be added at once
+#[allow(non_camel_case_types)]
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Fp_i<const P: u8, const N: u8> {
+  pub element: u32,
+}
+
+pub type F5_i<const N: u8> = Fp_i<5, N>;
+pub type F7_i<const N: u8> = Fp_i<7, N>;
+
+impl<const P: u8, const N: u8> FieldTraits for Fp_i<P, N> {
+  const ZERO: Self = Fp_i { element: 0 };
+  const ONE: Self = Fp_i { element: 1 };
+  const MAX: Self = Fp_i { element: (P as u32 - 1) * Self::ONES };
+  const CHARACTERISTIC: usize = P as usize;
+  const DEGREE: usize = N as usize;
+  const COEFF_BITS: usize = 4;
+
+  // Counting in base P, each nibble is a digit
+  fn next(self) -> Option<Self> {
+    if self == Self::MAX {
+      return None
+    }
+    let mut element = self.element;
+    for i in 0..N {
+      let digit = (element >> (4*i)) & 0xF;
+      if digit + 1 < P as u32 {
+        return Some(Fp_i { element: element + (1 << (4*i)) })
+      }
+      element &= !(0xF << (4*i));
+    }
+    None
+  }
+
+  fn to_bits(self) -> u64 {
+    self.element as u64
+  }
+
+  fn from_bits(bits: u64) -> Self {
+    Fp_i { element: bits as u32 }
+  }
+}
+
+impl<const P: u8, const N: u8> Fp_i<P, N> {
+  // A 1 in every nibble that is in use
+  const ONES: u32 = 0x1111_1111 >> (32 - 4*N as u32);
+
+  #[allow(dead_code)]
+  pub fn new(element: u32) -> Fp_i<P, N> {
+    Fp_i { element }
+  }
+
+  // print each coefficient as a number
+  #[allow(dead_code)]
+  pub fn print(&self) {
+    for j in 0..N {
+      let i = N - j - 1;
+      print!("{}", (self.element >> (4 * i)) & 0xF);
+    }
+    println!(" ({})", N);
+  }
+
+  fn internal_add(a: u32, b: u32) -> u32 {
+    // After adding, every nibble that reached P gets P subtracted again
+    // Adding 8 - P sets the highest bit of exactly those nibbles
+    let sum = a + b;
+    let overflow = ((sum + (8 - P as u32) * Self::ONES) >> 3) & Self::ONES;
+    sum - overflow * P as u32
+  }
+
+  // We first convert the polynomials to vectors with coefficients in Z/PZ
+  // Then we multiply them and reduce the result
+  fn internal_mul(a: u32, b: u32) -> u32 {
+    // Not from the Handbook of finite fields, whose tables used above only cover F2 and F3
+    // Found by a search in the ordering of those tables: the first irreducible x^N + c_(N-1) x^(N-1) + ... + c_0
+    // with (c_(N-1), ..., c_0) lexographically smallest, which reproduces the F3 table for N up to 6
+    // Stored as x^N = IRRED_POLY[N], so as -c_i in nibble i, irreducibility is checked in the tests below
+    const IRRED_POLY_5: [u64; 9] = [0x0, 0x4, 0x3, 0x44, 0x3, 0x14, 0x43, 0x44, 0x3];
+    const IRRED_POLY_7: [u64; 9] = [0x0, 0x6, 0x6, 0x5, 0x66, 0x64, 0x5, 0x16, 0x64];
+    let irred = match P {
+      5 => IRRED_POLY_5[N as usize],
+      7 => IRRED_POLY_7[N as usize],
+      _ => panic!("Field size not supported"),
+    };
+
+    let p = P as u32;
+    let mut product = [0u32; 16];
+    for i in 0..N as usize {
+      let lhs = (a >> (4*i)) & 0xF;
+      if lhs == 0 {
+        continue;
+      }
+      for j in 0..N as usize {
+        product[i + j] = (product[i + j] + lhs * ((b >> (4*j)) & 0xF)) % p;
+      }
+    }
+
+    // x^(N+i) = x^i * IRRED_POLY[N], so the highest digits get folded down
+    for i in (N as usize..2*N as usize).rev() {
+      let factor = product[i];
+      if factor == 0 {
+        continue;
+      }
+      product[i] = 0;
+      for j in 0..N as usize {
+        let digit = ((irred >> (4*j)) & 0xF) as u32;
+        product[i - N as usize + j] = (product[i - N as usize + j] + factor * digit) % p;
+      }
+    }
+
+    (0..N as usize).fold(0, |acc, i| acc | (product[i] << (4*i)))
+  }
+}
+
+impl<const P: u8, const N: u8> Add for Fp_i<P, N> {
+  type Output = Self;
+
+  fn add(self, rhs: Self) -> Self::Output {
+    Fp_i { element: Self::internal_add(self.element, rhs.element) }
+  }
+}
+
+impl<const P: u8, const N: u8> Mul for Fp_i<P, N> {
+  type Output = Self;
+
+  fn mul(self, rhs: Self) -> Self::Output {
+    Fp_i { element: Self::internal_mul(self.element, rhs.element) }
+  }
+}
+
+impl<const P: u8, const N: u8> AddAssign for Fp_i<P, N> {
+  fn add_assign(&mut self, rhs: Self) {
+    self.element = Self::internal_add(self.element, rhs.element);
+  }
+}
+
+impl<const P: u8, const N: u8> MulAssign for Fp_i<P, N> {
+  fn mul_assign(&mut self, rhs: Self) {
+    self.element = Self::internal_mul(self.element, rhs.element);
+  }
+}
+
+// Projective points over F_q^k up to the Frobenius x -> x^q of F_q^k over F_q
+// Points of P^2(F_q^k) are grouped into Frobenius orbits, a point in an orbit of size j is defined over F_q^j
+// Only points in an orbit of exactly size k are new compared to the subfields, and of those only the smallest one is yielded
+// The Frobenius keeps the shape of our normalized points (which coordinates are 0 or 1), so it can act coordinatewise
+// A clone resumes where the original was, streamed lookups keep one per block
+#[derive(Clone)]
+pub struct ClosedPointIterator<T: FieldTraits> {
+  points: ProjectivePointIterator<T>,
+  q: u64,
+  k: usize,
+}
+
+impl<T: FieldTraits> ClosedPointIterator<T> {
+  fn key(point: (T, T, T)) -> (u64, u64, u64) {
+    (point.0.to_bits(), point.1.to_bits(), point.2.to_bits())
+  }
+
+  fn is_representative(&self, point: (T, T, T)) -> bool {
+    let mut conjugate = point;
+    for _ in 1..self.k {
+      conjugate = (conjugate.0.pow(self.q), conjugate.1.pow(self.q), conjugate.2.pow(self.q));
+      if conjugate == point || Self::key(conjugate) < Self::key(point) {
+        return false
+      }
+    }
+    true
+  }
+}
+
+impl<T: FieldTraits> Iterator for ClosedPointIterator<T> {
+  type Item = (T, T, T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let point = self.points.next()?;
+      if self.is_representative(point) {
+        return Some(point)
+      }
+    }
+  }
+}
+
 impl<T: FieldTraits + Copy> Iterator for ProjectivePointIterator<T> {
     type Item = (T, T, T);
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.phase {
-            ProjectivePointPhase::START => {self.phase = ProjectivePointPhase::Z_NULL;   Some((T::ONE, T::ZERO, T::ZERO))},
-            ProjectivePointPhase::Z_NULL => {
+            ProjectivePointPhase::Start => {self.phase = ProjectivePointPhase::ZNull;   Some((T::ONE, T::ZERO, T::ZERO))},
+            ProjectivePointPhase::ZNull => {
                 let number = (self.x, T::ONE, T::ZERO);
                 let result = self.x.next();
                 if let Some(res) = result {
                   self.x = res;
                 } else {
-                  self.phase = ProjectivePointPhase::Z_ONE;
+                  self.phase = ProjectivePointPhase::ZOne;
                   self.x = T::ZERO;
                 }
                 Some(number)
               } ,
-            ProjectivePointPhase::Z_ONE => {
+            ProjectivePointPhase::ZOne => {
               let number = (self.x, self.y, T::ONE);
               let x = self.x.next();
               if let Some(x_res) = x {
@@ -311,12 +657,149 @@ impl<T: FieldTraits + Copy> Iterator for ProjectivePointIterator<T> {
                 if let Some(y_res) = y {
                   self.y = y_res;
                 } else {
-                  self.phase = ProjectivePointPhase::FINISHED;
+                  self.phase = ProjectivePointPhase::Finished;
                 }
               }
               Some(number)
             },
-            ProjectivePointPhase::FINISHED => None,
+            ProjectivePointPhase::Finished => None,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // The modulus f of F is irreducible iff x^(p^N) = x and x^(p^(N/r)) - x is a unit for every prime r | N (Rabin)
+  // The arithmetic of F is that of F_p[x]/(f) either way, and an element is a unit iff its (p^N - 1)-th power is one
+  fn check_irreducible<F: FieldTraits>() {
+    let p = F::CHARACTERISTIC as u64;
+    let n = F::DEGREE as u32;
+    if n == 1 {
+      return
+    }
+    let x = F::from_bits(1 << F::COEFF_BITS);
+    assert!(x.pow(p.pow(n)) == x, "x^(p^{}) is not x", n);
+    for r in (2..=n).filter(|r| n.is_multiple_of(*r) && (2..*r).all(|s| !r.is_multiple_of(s))) {
+      let g = x.pow(p.pow(n / r)) + x.neg();
+      assert!(g.pow(p.pow(n) - 1) == F::ONE, "modulus of degree {} has a factor of degree dividing {}", n, n / r);
+    }
+  }
+
+  fn count_elements<F: FieldTraits>() -> u64 {
+    let mut element = F::ZERO;
+    let mut count = 1;
+    while let Some(next) = element.next() {
+      element = next;
+      count += 1;
+    }
+    count
+  }
+
+  // Every nonzero element has an inverse, and multiplication is associative and distributes over addition
+  fn check_arithmetic<F: FieldTraits + std::fmt::Debug>() {
+    check_irreducible::<F>();
+    let elements: Vec<F> = std::iter::successors(Some(F::ZERO), |e| e.next()).collect();
+    assert_eq!(elements.len() as u64, (F::CHARACTERISTIC as u64).pow(F::DEGREE as u32));
+    for &a in &elements[1..] {
+      assert_eq!(a * a.inverse(), F::ONE, "{:?} has no inverse", a);
+      assert_eq!(a.pow(elements.len() as u64 - 1), F::ONE);
+    }
+    let sample: Vec<F> = elements.iter().step_by(elements.len().div_ceil(23)).copied().collect();
+    for &a in &sample {
+      for &b in &sample {
+        assert_eq!(a * b, b * a);
+        for &c in &sample {
+          assert_eq!((a * b) * c, a * (b * c));
+          assert_eq!(a * (b + c), a * b + a * c);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn prime_field_arithmetic() {
+    check_arithmetic::<F5_i<1>>();
+    check_arithmetic::<F7_i<1>>();
+    // 3 * 4 = 12 = 2 in F5 and 3 * 5 = 15 = 1 in F7
+    assert_eq!(F5_i::<1>::new(3) * F5_i::new(4), F5_i::new(2));
+    assert_eq!(F7_i::<1>::new(3) * F7_i::new(5), F7_i::ONE);
+  }
+
+  #[test]
+  fn extension_arithmetic() {
+    check_arithmetic::<F2_i<2>>();
+    check_arithmetic::<F2_i<3>>();
+    check_arithmetic::<F3_i<2>>();
+    check_arithmetic::<F5_i<2>>();
+    check_arithmetic::<F5_i<3>>();
+    check_arithmetic::<F7_i<2>>();
+    check_arithmetic::<F7_i<3>>();
+    // F4 = F2[w]/(w^2 + w + 1), so w * w = w + 1
+    assert_eq!(F2_i::<2>::new(0b10) * F2_i::new(0b10), F2_i::new(0b11));
+    // F8 = F2[w]/(w^3 + w + 1), so w^2 * w = w + 1
+    assert_eq!(F2_i::<3>::new(0b100) * F2_i::new(0b10), F2_i::new(0b11));
+    // F9 = F3[w]/(w^2 + 1), so w * w = 2
+    assert_eq!(F3_i::<2>::new(0b0100) * F3_i::new(0b0100), F3_i::new(0b10));
+    // F25 = F5[w]/(w^2 + 2), so w * w = 3
+    assert_eq!(F5_i::<2>::new(0x10) * F5_i::new(0x10), F5_i::new(3));
+  }
+
+  // Every modulus in the tables is irreducible, also the ones too large to check every element
+  #[test]
+  fn irreducible_moduli() {
+    fn check_all<const N: u8>() {
+      check_irreducible::<F2_i<N>>();
+      check_irreducible::<F3_i<N>>();
+      check_irreducible::<F5_i<N>>();
+      check_irreducible::<F7_i<N>>();
+    }
+    check_all::<2>();
+    check_all::<3>();
+    check_all::<4>();
+    check_all::<5>();
+    check_all::<6>();
+    check_all::<7>();
+    check_all::<8>();
+    check_irreducible::<F2_i<9>>();
+    check_irreducible::<F2_i<10>>();
+    check_irreducible::<F2_i<12>>();
+    check_irreducible::<F2_i<16>>();
+  }
+
+  // Writing an element of B in the basis is a one to one map B -> F that keeps addition and multiplication
+  fn check_embedding<B: FieldTraits + std::fmt::Debug, F: FieldTraits + std::fmt::Debug>() {
+    let basis = embed_basis::<B, F>();
+    let embed = |b: B| basis.iter().enumerate().fold(F::ZERO, |acc, (j, &w)| acc + w.scale(b.digit(j)));
+    let elements: Vec<B> = std::iter::successors(Some(B::ZERO), |e| e.next()).collect();
+    let images: Vec<F> = elements.iter().map(|&b| embed(b)).collect();
+    assert_eq!(embed(B::ONE), F::ONE);
+    for (i, &a) in elements.iter().enumerate() {
+      for (j, &b) in elements.iter().enumerate() {
+        assert_eq!(embed(a + b), images[i] + images[j], "{:?} + {:?}", a, b);
+        assert_eq!(embed(a * b), images[i] * images[j], "{:?} * {:?}", a, b);
+        assert!(i == j || images[i] != images[j], "{:?} and {:?} have the same image", a, b);
+      }
+    }
+  }
+
+  #[test]
+  fn subfield_embeddings() {
+    check_embedding::<F2_i<2>, F2_i<4>>();
+    check_embedding::<F2_i<2>, F2_i<6>>();
+    check_embedding::<F2_i<3>, F2_i<6>>();
+    check_embedding::<F3_i<2>, F3_i<4>>();
+    check_embedding::<F3_i<2>, F3_i<6>>();
+    check_embedding::<F5_i<1>, F5_i<3>>();
+  }
+
+  #[test]
+  fn f3_up_to_extension_10() {
+    check_irreducible::<F3_i<9>>();
+    check_irreducible::<F3_i<10>>();
+    assert_eq!(count_elements::<F3_i<10>>(), 3u64.pow(10));
+    let a = F3_i::<10>::new(0b10_0110_0001_1000_0110);
+    assert!(a * a.inverse() == F3_i::ONE);
+  }
+}
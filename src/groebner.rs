@@ -0,0 +1,292 @@
+use std::collections::BTreeMap;
+
+use crate::field_extensions::FieldTraits;
+use crate::polynomials::Term;
+
+
+//
+//
+// Exact smoothness test over the algebraic closure of F_q
+// A plane curve F = 0 is singular iff F, F_x, F_y and F_z have a common zero in P^2 over the algebraic closure
+// We cover P^2 by the affine chart z = 1, the line z = 0 (as y = 1) and the point (1:0:0)
+// On a chart the equations have no common zero iff their reduced Groebner basis is {1} (Nullstellensatz),
+// so the Groebner bases are the certificate, everything is computed over F_q itself
+//
+//
+
+
+// Monomial x^i y^j, ordered by total degree and then by the power of x (graded lexicographic with x > y)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Monomial {
+  degree: u32,
+  x: u32,
+}
+
+impl Monomial {
+  fn new(x: u32, y: u32) -> Monomial {
+    Monomial { degree: x + y, x }
+  }
+
+  fn y(self) -> u32 {
+    self.degree - self.x
+  }
+
+  fn divides(self, other: Monomial) -> bool {
+    self.x <= other.x && self.y() <= other.y()
+  }
+
+  fn lcm(self, other: Monomial) -> Monomial {
+    Monomial::new(self.x.max(other.x), self.y().max(other.y()))
+  }
+
+  fn div(self, other: Monomial) -> Monomial {
+    Monomial::new(self.x - other.x, self.y() - other.y())
+  }
+
+  fn mul(self, other: Monomial) -> Monomial {
+    Monomial::new(self.x + other.x, self.y() + other.y())
+  }
+}
+
+
+// Polynomial in x and y over F, the leading term is the last entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct Poly2<F: FieldTraits> {
+  terms: BTreeMap<Monomial, F>,
+}
+
+impl<F: FieldTraits> Poly2<F> {
+  fn zero() -> Poly2<F> {
+    Poly2 { terms: BTreeMap::new() }
+  }
+
+  fn add_term(&mut self, m: Monomial, c: F) {
+    let sum = *self.terms.get(&m).unwrap_or(&F::ZERO) + c;
+    if sum == F::ZERO {
+      self.terms.remove(&m);
+    } else {
+      self.terms.insert(m, sum);
+    }
+  }
+
+  fn is_zero(&self) -> bool {
+    self.terms.is_empty()
+  }
+
+  pub fn is_one(&self) -> bool {
+    self.terms.len() == 1 && self.terms.get(&Monomial::new(0, 0)) == Some(&F::ONE)
+  }
+
+  fn leading(&self) -> (Monomial, F) {
+    let (&m, &c) = self.terms.last_key_value().expect("Zero polynomial has no leading term");
+    (m, c)
+  }
+
+  fn monic(&self) -> Poly2<F> {
+    let inv = self.leading().1.inverse();
+    Poly2 { terms: self.terms.iter().map(|(&m, &c)| (m, c * inv)).collect() }
+  }
+
+  // self - c m g
+  fn subtract_multiple(&mut self, c: F, m: Monomial, g: &Poly2<F>) {
+    let c = c.neg();
+    for (&n, &d) in &g.terms {
+      self.add_term(m.mul(n), c * d);
+    }
+  }
+
+  // Full reduction by a list of polynomials, every term of the result is irreducible
+  fn reduce(&self, basis: &[Poly2<F>]) -> Poly2<F> {
+    let mut rest = self.clone();
+    let mut res = Poly2::zero();
+    while !rest.is_zero() {
+      let (m, c) = rest.leading();
+      match basis.iter().find(|g| g.leading().0.divides(m)) {
+        Some(g) => {
+          let (n, d) = g.leading();
+          rest.subtract_multiple(c * d.inverse(), m.div(n), g);
+        },
+        None => {
+          rest.terms.remove(&m);
+          res.add_term(m, c);
+        },
+      }
+    }
+    res
+  }
+
+  fn s_polynomial(f: &Poly2<F>, g: &Poly2<F>) -> Poly2<F> {
+    let (m, c) = f.leading();
+    let (n, d) = g.leading();
+    let lcm = m.lcm(n);
+    let mut s = Poly2::zero();
+    s.subtract_multiple(c.inverse().neg(), lcm.div(m), f);
+    s.subtract_multiple(d.inverse(), lcm.div(n), g);
+    s
+  }
+
+  pub fn str(&self) -> String {
+    if self.is_zero() {
+      return "0".to_owned()
+    }
+    let terms: Vec<String> = self.terms.iter().rev().map(|(m, c)| {
+      let coefficient = crate::polynomials::bits_to_constant::<F>(c.to_bits());
+      let variables = [("x", m.x), ("y", m.y())].iter().filter(|(_, e)| *e > 0)
+        .map(|(v, e)| if *e == 1 { v.to_string() } else { format!("{}^{}", v, e) })
+        .collect::<Vec<String>>().join("");
+      match (coefficient, variables.is_empty()) {
+        (_, true) => format!("{}", coefficient),
+        (1, false) => variables,
+        _ => format!("{}{}", coefficient, variables),
+      }
+    }).collect();
+    terms.join(" + ")
+  }
+}
+
+
+// Buchberger's algorithm, pairs with coprime leading monomials are skipped (Buchberger's first criterion)
+// Returns the reduced Groebner basis, which is [1] exactly when the equations have no common zero
+pub fn groebner_basis<F: FieldTraits>(generators: Vec<Poly2<F>>) -> Vec<Poly2<F>> {
+  let mut basis: Vec<Poly2<F>> = generators.into_iter().filter(|g| !g.is_zero()).map(|g| g.monic()).collect();
+  let mut pairs: Vec<(usize, usize)> = (0..basis.len()).flat_map(|j| (0..j).map(move |i| (i, j))).collect();
+
+  while let Some((i, j)) = pairs.pop() {
+    let (m, n) = (basis[i].leading().0, basis[j].leading().0);
+    if m.lcm(n) == m.mul(n) {
+      continue;
+    }
+    let s = Poly2::s_polynomial(&basis[i], &basis[j]).reduce(&basis);
+    if !s.is_zero() {
+      basis.push(s.monic());
+      let new = basis.len() - 1;
+      pairs.extend((0..new).map(|i| (i, new)));
+    }
+  }
+
+  // Minimalize, then reduce every element by the others
+  let mut minimal: Vec<Poly2<F>> = vec![];
+  for (i, g) in basis.iter().enumerate() {
+    let m = g.leading().0;
+    let redundant = basis.iter().enumerate().any(|(j, h)| {
+      let n = h.leading().0;
+      j != i && n.divides(m) && (n != m || j < i)
+    });
+    if !redundant {
+      minimal.push(g.clone());
+    }
+  }
+  let mut reduced = vec![];
+  for i in 0..minimal.len() {
+    let others: Vec<Poly2<F>> = minimal.iter().enumerate().filter(|&(j, _)| j != i).map(|(_, g)| g.clone()).collect();
+    reduced.push(minimal[i].reduce(&others).monic());
+  }
+  reduced.sort_by_key(|g| g.leading().0);
+  reduced
+}
+
+// Amount of common zeros counted with multiplicity, so the dimension of the quotient ring
+// None if there are infinitely many, which happens when the curve has a multiple component
+fn quotient_dimension<F: FieldTraits>(basis: &[Poly2<F>]) -> Option<usize> {
+  let leading: Vec<Monomial> = basis.iter().map(|g| g.leading().0).collect();
+  let max_x = leading.iter().filter(|m| m.y() == 0).map(|m| m.x).min()?;
+  let max_y = leading.iter().filter(|m| m.x == 0).map(|m| m.y()).min()?;
+  Some((0..max_x).flat_map(|x| (0..max_y).map(move |y| Monomial::new(x, y)))
+    .filter(|&m| !leading.iter().any(|l| l.divides(m)))
+    .count())
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Chart {
+  // z = 1
+  Affine,
+  // z = 0, y = 1
+  Line,
+  // (1:0:0)
+  Point,
+}
+
+impl Chart {
+  // The monomial a term becomes on the chart, None if it vanishes there
+  fn restrict(self, t: &Term) -> Option<Monomial> {
+    match self {
+      Chart::Affine => Some(Monomial::new(t.x_deg as u32, t.y_deg as u32)),
+      Chart::Line => (t.z_deg == 0).then(|| Monomial::new(t.x_deg as u32, 0)),
+      Chart::Point => (t.y_deg == 0 && t.z_deg == 0).then(|| Monomial::new(0, 0)),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SmoothnessCertificate<F: FieldTraits> {
+  // The reduced Groebner bases of all charts are [1]
+  Smooth,
+  // The first chart with a common zero, the reduced Groebner basis there and the amount of singular points in it with multiplicity
+  Singular { chart: Chart, basis: Vec<Poly2<F>>, singular_points: Option<usize> },
+}
+
+impl<F: FieldTraits> SmoothnessCertificate<F> {
+  pub fn is_smooth(&self) -> bool {
+    *self == SmoothnessCertificate::Smooth
+  }
+
+  pub fn str(&self) -> String {
+    match self {
+      SmoothnessCertificate::Smooth => "smooth".to_owned(),
+      SmoothnessCertificate::Singular { chart, basis, singular_points } => {
+        let basis: Vec<String> = basis.iter().map(|g| g.str()).collect();
+        let points = singular_points.map_or("infinitely many".to_owned(), |n| n.to_string());
+        format!("singular on chart {:?} with {} singular points, Groebner basis [{}]", chart, points, basis.join(", "))
+      },
+    }
+  }
+}
+
+
+// The partial derivatives of a homogeneous polynomial, given by its coefficients in lookup table order
+fn partial_derivatives<F: FieldTraits>(coefficients: &[F], lut: &[Term]) -> [Vec<(Term, F)>; 4] {
+  let mut res: [Vec<(Term, F)>; 4] = Default::default();
+  for (&c, &t) in coefficients.iter().zip(lut) {
+    if c == F::ZERO {
+      continue;
+    }
+    res[0].push((t, c));
+    if t.x_deg > 0 {
+      res[1].push((Term { x_deg: t.x_deg - 1, ..t }, c.scale(t.x_deg as u64)));
+    }
+    if t.y_deg > 0 {
+      res[2].push((Term { y_deg: t.y_deg - 1, ..t }, c.scale(t.y_deg as u64)));
+    }
+    if t.z_deg > 0 {
+      res[3].push((Term { z_deg: t.z_deg - 1, ..t }, c.scale(t.z_deg as u64)));
+    }
+  }
+  res
+}
+
+pub fn is_smooth_exact<F: FieldTraits>(coefficients: &[F], lut: &[Term]) -> SmoothnessCertificate<F> {
+  let equations = partial_derivatives(coefficients, lut);
+
+  for chart in [Chart::Affine, Chart::Line, Chart::Point] {
+    let generators = equations.iter().map(|terms| {
+      let mut poly = Poly2::zero();
+      for (t, c) in terms {
+        if let Some(m) = chart.restrict(t) {
+          poly.add_term(m, *c);
+        }
+      }
+      poly
+    }).collect();
+    let basis = groebner_basis(generators);
+    if !(basis.len() == 1 && basis[0].is_one()) {
+      let singular_points = match chart {
+        Chart::Affine => quotient_dimension(&basis),
+        Chart::Line => basis.first().map(|g| g.leading().0.x as usize),
+        Chart::Point => Some(1),
+      };
+      return SmoothnessCertificate::Singular { chart, basis, singular_points }
+    }
+  }
+  SmoothnessCertificate::Smooth
+}
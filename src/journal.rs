@@ -0,0 +1,190 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+
+use crate::algebraic_types::{IsoPolynomial, PolynomialResult};
+use crate::config::Config;
+
+
+//
+//
+// Journal of the chunks that are done, so a run that crashes can be resumed with --resume
+// The first line identifies the run, a journal of a different run is never merged
+// Every following line is one chunk: "first class | points per extension | points of every class in the chunk"
+// The points of a class are separated by commas and the classes by semicolons, in the order of the input file
+// A line is only complete once it ends in a newline, a partial last line from a crash is dropped
+//
+//
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkRecord {
+  // Index in the input file of the first class of the chunk
+  pub start: usize,
+  pub count: Vec<usize>,
+  pub points_on_curve: Vec<Vec<usize>>,
+}
+
+fn join(values: &[usize]) -> String {
+  values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(",")
+}
+
+fn split(values: &str) -> Option<Vec<usize>> {
+  if values.is_empty() {
+    return Some(vec![])
+  }
+  values.split(',').map(|v| v.parse().ok()).collect()
+}
+
+impl ChunkRecord {
+  pub fn new<const W: usize>(start: usize, count: Vec<usize>, results: &[PolynomialResult<W>]) -> ChunkRecord {
+    ChunkRecord { start, count, points_on_curve: results.iter().map(|r| r.points_on_curve.clone()).collect() }
+  }
+
+  pub fn end(&self) -> usize {
+    self.start + self.points_on_curve.len()
+  }
+
+  // A count for every extension, and for every class the points up to its first singular extension at most
+  pub fn matches_extensions(&self, extensions: usize) -> bool {
+    self.count.len() == extensions && self.points_on_curve.iter().all(|p| p.len() <= extensions)
+  }
+
+  // The results of the classes of the chunk, in the order of the input file
+  pub fn results<const W: usize>(self, iso_polys: &[IsoPolynomial<W>]) -> Vec<PolynomialResult<W>> {
+    let start = self.start;
+    self.points_on_curve.into_iter().enumerate().map(|(i, points)| PolynomialResult::new(iso_polys[start + i], points)).collect()
+  }
+
+  pub fn line(&self) -> String {
+    let points: Vec<String> = self.points_on_curve.iter().map(|p| join(p)).collect();
+    format!("{} | {} | {}", self.start, join(&self.count), points.join(";"))
+  }
+
+  pub fn parse(line: &str) -> Option<ChunkRecord> {
+    let mut fields = line.split(" | ");
+    let start = fields.next()?.parse().ok()?;
+    let count = split(fields.next()?)?;
+    let points_on_curve = fields.next()?.split(';').map(split).collect::<Option<_>>()?;
+    if fields.next().is_some() {
+      return None
+    }
+    Some(ChunkRecord { start, count, points_on_curve })
+  }
+}
+
+
+pub struct Journal {
+  file: File,
+}
+
+impl Journal {
+  // The identity of the run, see Config::run_identity, so the same file reached through another path or a symlink resumes as well
+  pub fn header(config: &Config, input: &str, classes: usize) -> String {
+    format!("# journal | {}", config.run_identity(input, classes))
+  }
+
+  // Overwrites an existing journal, Config only allows that with --fresh
+  pub fn create(path: &str, header: &str) -> Journal {
+    Journal::write(path, header, &[])
+  }
+
+  // The chunks of an earlier run with the same header, the journal is rewritten without a partial last line
+  pub fn resume(path: &str, header: &str) -> Result<(Journal, Vec<ChunkRecord>), String> {
+    let contents = match fs::read_to_string(path) {
+      Ok(contents) => contents,
+      Err(_) => {
+        println!("No journal found at {}, starting from the beginning", path);
+        return Ok((Journal::create(path, header), vec![]))
+      },
+    };
+
+    let mut lines: Vec<&str> = contents.split('\n').collect();
+    // Whatever follows the last newline was never completed
+    lines.pop();
+    let mut lines = lines.into_iter().enumerate();
+    match lines.next() {
+      Some((_, line)) if line == header => {},
+      _ => return Err(format!("Journal {} belongs to a different run, expected header '{}'", path, header)),
+    }
+
+    let mut records = vec![];
+    for (i, line) in lines {
+      records.push(ChunkRecord::parse(line).ok_or(format!("Journal {} has an invalid chunk on line {}", path, i + 1))?);
+    }
+    Ok((Journal::write(path, header, &records), records))
+  }
+
+  fn write(path: &str, header: &str, records: &[ChunkRecord]) -> Journal {
+    let mut lines = vec![header.to_owned()];
+    lines.extend(records.iter().map(|r| r.line()));
+    fs::write(path, lines.join("\n") + "\n").expect("Unable to write journal");
+    let file = OpenOptions::new().append(true).open(path).expect("Unable to open journal");
+    Journal { file }
+  }
+
+  pub fn append(&mut self, record: &ChunkRecord) {
+    writeln!(self.file, "{}", record.line()).expect("Unable to write journal");
+    self.file.sync_data().expect("Unable to write journal");
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn config(input: &str) -> Config {
+    let args = ["--degree", "3", "--field", "2", "--input", input, "--fresh"];
+    Config::from_args(args.iter().map(|a| a.to_string())).unwrap()
+  }
+
+  #[test]
+  fn record_round_trip() {
+    let record = ChunkRecord { start: 12, count: vec![3, 5], points_on_curve: vec![vec![3, 5], vec![], vec![1]] };
+    assert_eq!(record.line(), "12 | 3,5 | 3,5;;1");
+    assert_eq!(ChunkRecord::parse(&record.line()), Some(record.clone()));
+    assert_eq!(record.end(), 15);
+    assert_eq!(ChunkRecord::parse("12 | 3,5 | 3,x"), None);
+    assert!(record.matches_extensions(2));
+    assert!(!record.matches_extensions(3));
+    // Points over three extensions in a run of two
+    assert!(!ChunkRecord::parse("12 | 3,5 | 3,5,9;;1").unwrap().matches_extensions(2));
+  }
+
+  // A crash leaves a partial last line, resuming drops it and keeps the complete chunks, also for another path to the input
+  #[test]
+  fn resume_after_crash() {
+    let path = std::env::temp_dir().join(format!("affiene_variety-journal-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    let header = Journal::header(&config("input/3-2.txt"), "3 | 2\n", 5);
+    assert_eq!(header, format!("# journal | {}", config("input/3-2.txt").run_identity("3 | 2\n", 5)));
+    assert_eq!(header, Journal::header(&config("./input/../input/3-2.txt"), "3 | 2\n", 5));
+    assert_ne!(header, Journal::header(&config("input/3-2.txt"), "3 | 2\n# edited\n", 5));
+
+    let records = [
+      ChunkRecord { start: 2, count: vec![1, 2], points_on_curve: vec![vec![3, 5], vec![1]] },
+      ChunkRecord { start: 0, count: vec![0, 1], points_on_curve: vec![vec![], vec![3, 5]] },
+    ];
+    let mut journal = Journal::create(path, &header);
+    for record in &records {
+      journal.append(record);
+    }
+    drop(journal);
+    let mut file = OpenOptions::new().append(true).open(path).unwrap();
+    write!(file, "4 | 1,1 | 3").unwrap();
+    drop(file);
+
+    let (mut journal, resumed) = Journal::resume(path, &header).unwrap();
+    assert_eq!(resumed, records);
+    assert_eq!(fs::read_to_string(path).unwrap(), format!("{}\n{}\n{}\n", header, records[0].line(), records[1].line()));
+
+    let last = ChunkRecord { start: 4, count: vec![1, 1], points_on_curve: vec![vec![3, 5]] };
+    journal.append(&last);
+    drop(journal);
+    assert_eq!(Journal::resume(path, &header).unwrap().1, [records[0].clone(), records[1].clone(), last]);
+
+    let other = Journal::header(&config("input/3-2.txt"), "3 | 2\n", 6);
+    assert!(Journal::resume(path, &other).is_err());
+    fs::remove_file(path).unwrap();
+  }
+}
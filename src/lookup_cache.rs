@@ -0,0 +1,264 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::algebraic_types::{BaseEmbedding, Lookup};
+use crate::checksum::Fnv1a;
+use crate::field_extensions::FieldTraits;
+use crate::lookup_table::{PackedTable, CACHE_LINE};
+use crate::polynomials::{field_order, Term};
+use mapping::Mapping;
+
+
+//
+//
+// On disk cache of the lookup tables, one file per (q, k, degree, irreducible polynomial of F_q^k)
+// Layout, all numbers little endian: MAGIC, VERSION, the table sizes and the key as u64, padded to HEADER_BYTES,
+// the words of the packed table (see lookup_table) padded to a whole cache line, the points with every element as a u32,
+// and at the end an FNV-1a hash of the header, the table words and the points
+// The whole table is hashed, a corrupted row would otherwise be used silently and give wrong counts
+// Files are memory mapped on unix and the table is used in place, a file with a different version, key, size or hash is rebuilt
+//
+//
+
+
+const MAGIC: &[u8; 8] = b"AVLOOKUP";
+// Raise whenever the layout, the order of the points or the derivative tables change
+const VERSION: u64 = 4;
+const HEADER_FIELDS: usize = 8;
+// The table starts on a cache line
+const HEADER_BYTES: usize = 2 * CACHE_LINE;
+
+
+#[cfg(unix)]
+pub mod mapping {
+  use std::ffi::c_void;
+  use std::fs::File;
+  use std::os::unix::io::AsRawFd;
+
+  extern "C" {
+    fn mmap(addr: *mut c_void, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> i32;
+  }
+
+  const PROT_READ: i32 = 1;
+  const MAP_PRIVATE: i32 = 2;
+
+  // A read only mapping of a whole file
+  pub struct Mapping {
+    ptr: *mut c_void,
+    len: usize,
+  }
+
+  impl Mapping {
+    pub fn open(path: &str) -> Option<Mapping> {
+      let file = File::open(path).ok()?;
+      let len = file.metadata().ok()?.len() as usize;
+      if len == 0 {
+        return None
+      }
+      // SAFETY: a private read only mapping of an open file, the file may be closed afterwards
+      let ptr = unsafe { mmap(std::ptr::null_mut(), len, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0) };
+      // MAP_FAILED
+      if ptr as isize == -1 {
+        return None
+      }
+      Some(Mapping { ptr, len })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+      // SAFETY: the mapping is len bytes long and lives as long as self
+      unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+  }
+
+  // SAFETY: the mapping is read only and unmapped only on drop
+  unsafe impl Send for Mapping {}
+  unsafe impl Sync for Mapping {}
+
+  impl Drop for Mapping {
+    fn drop(&mut self) {
+      // SAFETY: ptr and len come from a successful mmap
+      unsafe { munmap(self.ptr, self.len) };
+    }
+  }
+}
+
+#[cfg(not(unix))]
+pub mod mapping {
+  pub struct Mapping {
+    bytes: Vec<u8>,
+  }
+
+  impl Mapping {
+    pub fn open(path: &str) -> Option<Mapping> {
+      std::fs::read(path).ok().map(|bytes| Mapping { bytes })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+      &self.bytes
+    }
+  }
+}
+
+
+// Identifies the tables of a file, everything they are computed from
+fn key<B: FieldTraits, F: FieldTraits>(terms: &[Term]) -> [u64; 4] {
+  let degree = terms.first().map_or(0, |t| (t.x_deg + t.y_deg + t.z_deg) as u64);
+  [field_order::<B>(), (F::DEGREE / B::DEGREE) as u64, degree, F::reduced_modulus()]
+}
+
+pub fn path<B: FieldTraits, F: FieldTraits>(dir: &str, terms: &[Term]) -> String {
+  let [q, k, degree, modulus] = key::<B, F>(terms);
+  format!("{}/lookup-q{}-k{}-d{}-m{:x}.bin", dir, q, k, degree, modulus)
+}
+
+
+fn read_u64(bytes: &[u8], i: usize) -> u64 {
+  u64::from_le_bytes(bytes[8 * i..8 * i + 8].try_into().unwrap())
+}
+
+fn read_elements<F: FieldTraits>(bytes: &[u8]) -> impl Iterator<Item = F> + '_ {
+  bytes.chunks_exact(4).map(|e| F::from_bits(u32::from_le_bytes(e.try_into().unwrap()) as u64))
+}
+
+// Adds the words of a table to the hash as they are stored in the file
+fn hash_words(hasher: &mut Fnv1a, words: &[u64]) {
+  for w in words {
+    hasher.write(&w.to_le_bytes());
+  }
+}
+
+
+impl<F: FieldTraits> Lookup<F> {
+  fn header<B: FieldTraits>(&self, table: &PackedTable<F>) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    let sizes = [VERSION, table.rows as u64, table.columns as u64, self.affine_start as u64];
+    for field in sizes.iter().chain(&key::<B, F>(&self.terms)) {
+      bytes.extend_from_slice(&field.to_le_bytes());
+    }
+    bytes.resize(HEADER_BYTES, 0);
+    bytes
+  }
+
+  // Streams the file, so saving needs no second copy of the table
+  fn write<B: FieldTraits>(&self, table: &PackedTable<F>, out: &mut impl Write) -> std::io::Result<()> {
+    let header = self.header::<B>(table);
+    let words = &table.words()[..PackedTable::<F>::word_count(table.rows, table.columns)];
+    let mut hasher = Fnv1a::new();
+    hasher.write(&header);
+    hash_words(&mut hasher, words);
+
+    out.write_all(&header)?;
+    for w in words {
+      out.write_all(&w.to_le_bytes())?;
+    }
+    out.write_all(&vec![0; PackedTable::<F>::size(table.rows, table.columns) - 8 * words.len()])?;
+    for &(x, y, z) in &self.points {
+      for e in [x, y, z] {
+        let bytes = (e.to_bits() as u32).to_le_bytes();
+        hasher.write(&bytes);
+        out.write_all(&bytes)?;
+      }
+    }
+    out.write_all(&hasher.finish().to_le_bytes())
+  }
+
+  fn from_mapping<B: FieldTraits>(mapping: Arc<Mapping>, terms: &[Term]) -> Option<Lookup<F>> {
+    let bytes = mapping.bytes();
+    if bytes.len() < HEADER_BYTES + 8 || &bytes[..MAGIC.len()] != MAGIC {
+      return None
+    }
+    let (contents, hash) = bytes.split_at(bytes.len() - 8);
+
+    let fields = &contents[MAGIC.len()..];
+    let (rows, columns, affine_start) = (read_u64(fields, 1) as usize, read_u64(fields, 2) as usize, read_u64(fields, 3) as usize);
+    let file_key: Vec<u64> = (4..HEADER_FIELDS).map(|i| read_u64(fields, i)).collect();
+    if read_u64(fields, 0) != VERSION || file_key != key::<B, F>(terms) || columns != terms.len() {
+      return None
+    }
+    let points_start = HEADER_BYTES + PackedTable::<F>::size(rows, columns);
+    if contents.len() != points_start + 12 * rows {
+      return None
+    }
+
+    // Mapped words are read in place, which needs the byte order of the file
+    let table = if cfg!(all(unix, target_endian = "little")) {
+      PackedTable::mapped(mapping.clone(), HEADER_BYTES, rows, columns)?
+    } else {
+      PackedTable::from_le_bytes(&contents[HEADER_BYTES..points_start], rows, columns)
+    };
+    let mut hasher = Fnv1a::new();
+    hasher.write(&contents[..HEADER_BYTES]);
+    hash_words(&mut hasher, &table.words()[..PackedTable::<F>::word_count(rows, columns)]);
+    hasher.write(&contents[points_start..]);
+    if hasher.finish() != u64::from_le_bytes(hash.try_into().unwrap()) {
+      return None
+    }
+
+    let points = read_elements(&contents[points_start..]).collect::<Vec<F>>().chunks_exact(3).map(|p| (p[0], p[1], p[2])).collect();
+    Some(Lookup {
+      table: Some(table),
+      terms: terms.to_vec(),
+      block_rows: rows,
+      embedding: BaseEmbedding::new::<B>(),
+      affine_start,
+      points,
+      block_starts: vec![],
+      rows,
+      k: F::DEGREE / B::DEGREE,
+    })
+  }
+
+  // None if there is no valid file for these tables, terms are the monomials followed by their derivatives
+  pub fn load<B: FieldTraits>(dir: &str, terms: &[Term]) -> Option<Lookup<F>> {
+    let mapping = Mapping::open(&path::<B, F>(dir, terms))?;
+    Lookup::from_mapping::<B>(Arc::new(mapping), terms)
+  }
+
+  // Written next to the final file and then renamed, so a crash never leaves a partial file behind
+  // Streamed lookups have no table to save
+  pub fn save<B: FieldTraits>(&self, dir: &str) {
+    let Some(table) = &self.table else { return };
+    let path = path::<B, F>(dir, &self.terms);
+    let temporary = format!("{}.tmp", path);
+    fs::create_dir_all(Path::new(dir)).expect("Unable to create lookup cache directory");
+    let mut out = BufWriter::new(File::create(&temporary).expect("Unable to write lookup cache"));
+    self.write::<B>(table, &mut out).and_then(|_| out.flush()).expect("Unable to write lookup cache");
+    fs::rename(&temporary, &path).expect("Unable to write lookup cache");
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::field_extensions::F3_i;
+  use crate::polynomials::Polynomial;
+
+  // A flipped bit anywhere in the table makes the file invalid, not only in the first words
+  #[test]
+  fn corrupted_table_is_rebuilt() {
+    let dir = std::env::temp_dir().join(format!("affiene_variety-lookup-cache-{}", std::process::id()));
+    let dir = dir.to_str().unwrap();
+    let normal = Polynomial::<1>::generate_default_lut(3);
+    let (part_x, part_y, part_z) = Polynomial::<1>::generate_derative_luts(&normal, 3);
+    let lookup = Lookup::<F3_i<5>>::create::<F3_i<1>>(&normal, &part_x, &part_y, &part_z, None, None);
+    lookup.save::<F3_i<1>>(dir);
+
+    let loaded = Lookup::<F3_i<5>>::load::<F3_i<1>>(dir, &lookup.terms).expect("Saved tables do not load");
+    assert_eq!(loaded.points, lookup.points);
+    let (table, loaded_table) = (lookup.table.as_ref().unwrap(), loaded.table.as_ref().unwrap());
+    assert!((0..table.rows).all(|r| (0..table.columns).all(|c| table.row(r).get(c) == loaded_table.row(r).get(c))));
+    drop(loaded);
+
+    let file = path::<F3_i<1>, F3_i<5>>(dir, &lookup.terms);
+    let mut bytes = fs::read(&file).unwrap();
+    let words = PackedTable::<F3_i<5>>::word_count(table.rows, table.columns);
+    bytes[HEADER_BYTES + 8 * (words * 2 / 3) + 3] ^= 0b100;
+    fs::write(&file, bytes).unwrap();
+    assert!(Lookup::<F3_i<5>>::load::<F3_i<1>>(dir, &lookup.terms).is_none());
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
@@ -0,0 +1,206 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::field_extensions::FieldTraits;
+use crate::lookup_cache::mapping::Mapping;
+use crate::polynomials::Term;
+
+
+//
+//
+// Flat storage of the values of the monomials and their derivatives in the closed points
+// A row holds the values of all terms in one point, the rows follow each other without gaps in a single allocation
+// Every element takes exactly DEGREE * COEFF_BITS bits, so an element may straddle two words
+// The allocation starts on a cache line, and a table read from the lookup cache is used straight from the mapping
+//
+//
+
+
+pub const CACHE_LINE: usize = 64;
+const LINE_WORDS: usize = CACHE_LINE / 8;
+
+#[repr(align(64))]
+#[derive(Copy, Clone)]
+struct Line([u64; LINE_WORDS]);
+
+enum Words {
+  Owned(Vec<Line>),
+  // A mapped file, the words start at a byte offset that is a multiple of CACHE_LINE
+  Mapped(Arc<Mapping>, usize, usize),
+}
+
+pub struct PackedTable<F: FieldTraits> {
+  words: Words,
+  pub rows: usize,
+  pub columns: usize,
+  _field: PhantomData<F>,
+}
+
+impl<F: FieldTraits> PackedTable<F> {
+  pub const BITS: usize = F::DEGREE * F::COEFF_BITS;
+
+  // Amount of 64 bit words a table of this size takes
+  pub fn word_count(rows: usize, columns: usize) -> usize {
+    (rows * columns * Self::BITS).div_ceil(64)
+  }
+
+  pub fn size(rows: usize, columns: usize) -> usize {
+    Self::word_count(rows, columns).div_ceil(LINE_WORDS) * CACHE_LINE
+  }
+
+  // The values of every term in every point
+  pub fn generate(terms: &[Term], points: &[(F, F, F)]) -> PackedTable<F> {
+    let (rows, columns) = (points.len(), terms.len());
+    let mut lines = vec![Line([0; LINE_WORDS]); Self::word_count(rows, columns).div_ceil(LINE_WORDS)];
+    let mut bit = 0;
+    for &(x, y, z) in points {
+      for t in terms {
+        let value = t.evaluate(x, y, z).to_bits();
+        let (w, s) = (bit / 64, bit % 64);
+        lines[w / LINE_WORDS].0[w % LINE_WORDS] |= value << s;
+        if s + Self::BITS > 64 {
+          lines[(w + 1) / LINE_WORDS].0[(w + 1) % LINE_WORDS] |= value >> (64 - s);
+        }
+        bit += Self::BITS;
+      }
+    }
+    PackedTable { words: Words::Owned(lines), rows, columns, _field: PhantomData }
+  }
+
+  // A table stored in a mapped file at a byte offset that is a multiple of CACHE_LINE, None if it does not fit
+  pub fn mapped(mapping: Arc<Mapping>, offset: usize, rows: usize, columns: usize) -> Option<PackedTable<F>> {
+    let len = Self::word_count(rows, columns);
+    if !offset.is_multiple_of(CACHE_LINE) || offset + 8 * len > mapping.bytes().len() {
+      return None
+    }
+    Some(PackedTable { words: Words::Mapped(mapping, offset, len), rows, columns, _field: PhantomData })
+  }
+
+  // A copy of a table stored as little endian words
+  pub fn from_le_bytes(bytes: &[u8], rows: usize, columns: usize) -> PackedTable<F> {
+    let mut lines = vec![Line([0; LINE_WORDS]); Self::word_count(rows, columns).div_ceil(LINE_WORDS)];
+    for (i, w) in bytes.chunks_exact(8).take(Self::word_count(rows, columns)).enumerate() {
+      lines[i / LINE_WORDS].0[i % LINE_WORDS] = u64::from_le_bytes(w.try_into().unwrap());
+    }
+    PackedTable { words: Words::Owned(lines), rows, columns, _field: PhantomData }
+  }
+
+  pub fn words(&self) -> &[u64] {
+    match &self.words {
+      // SAFETY: a Line is LINE_WORDS words without padding
+      Words::Owned(lines) => unsafe { std::slice::from_raw_parts(lines.as_ptr() as *const u64, lines.len() * LINE_WORDS) },
+      // SAFETY: the mapping is page aligned and offset a multiple of CACHE_LINE, and mapped() checked the length
+      // The cache is little endian, so only little endian machines map it, see lookup_cache
+      Words::Mapped(mapping, offset, len) => unsafe { std::slice::from_raw_parts(mapping.bytes().as_ptr().add(*offset) as *const u64, *len) },
+    }
+  }
+
+  // The values of all terms in the point of a row
+  #[inline]
+  pub fn row(&self, row: usize) -> Row<'_, F> {
+    assert!(row < self.rows, "Row {} outside of a table with {} rows", row, self.rows);
+    Row { words: self.words(), bit: row * self.columns * Self::BITS, columns: self.columns, _field: PhantomData }
+  }
+}
+
+pub struct Row<'a, F: FieldTraits> {
+  words: &'a [u64],
+  bit: usize,
+  columns: usize,
+  _field: PhantomData<F>,
+}
+
+impl<F: FieldTraits> Row<'_, F> {
+  #[inline]
+  pub fn get(&self, column: usize) -> F {
+    assert!(column < self.columns);
+    let bits = PackedTable::<F>::BITS;
+    let bit = self.bit + column * bits;
+    let (w, s) = (bit / 64, bit % 64);
+    // SAFETY: the row and column are inside the table, and the words cover all rows * columns * BITS bits of it
+    // An element that straddles two words ends before the last bit, so word w + 1 exists as well
+    let mut value = unsafe { *self.words.get_unchecked(w) } >> s;
+    if s + bits > 64 {
+      value |= unsafe { *self.words.get_unchecked(w + 1) } << (64 - s);
+    }
+    F::from_bits(value & ((1 << bits) - 1))
+  }
+}
+
+fn moebius(mut n: usize) -> i64 {
+  let mut res = 1;
+  let mut p = 2;
+  while p * p <= n {
+    if n.is_multiple_of(p) {
+      n /= p;
+      if n.is_multiple_of(p) {
+        return 0
+      }
+      res = -res;
+    }
+    p += 1;
+  }
+  if n > 1 { -res } else { res }
+}
+
+// Amount of closed points of exact degree k in P^2 over F_q, so the amount of rows of a lookup table
+pub fn closed_point_count(q: u64, k: usize) -> usize {
+  let points = |d: usize| (q.pow(2 * d as u32) + q.pow(d as u32) + 1) as i64;
+  let sum: i64 = (1..=k).filter(|d| k.is_multiple_of(*d)).map(|d| moebius(k / d) * points(d)).sum();
+  (sum / k as i64) as usize
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashSet;
+  use crate::field_extensions::{F2_i, F3_i, F5_i};
+
+  #[test]
+  fn moebius_values() {
+    let values: Vec<i64> = (1..=12).map(moebius).collect();
+    assert_eq!(values, vec![1, -1, -1, 0, -1, 1, -1, 0, 0, 1, -1, 0]);
+  }
+
+  // The iterator over F = F_q^k yields closed_point_count(q, k) points, each from a different Frobenius orbit of size k
+  // Every point of P^2(F) lies in the orbit of a closed point of degree j | k, so they add up to q^2k + q^k + 1
+  fn check_closed_points<F: FieldTraits>(q: u64, k: usize) {
+    let key = |p: (F, F, F)| (p.0.to_bits(), p.1.to_bits(), p.2.to_bits());
+    let frobenius = |p: (F, F, F)| (p.0.pow(q), p.1.pow(q), p.2.pow(q));
+    let mut seen = HashSet::new();
+    let mut yielded = 0;
+    for point in F::iterate_over_closed_points(q, k) {
+      let orbit: Vec<(F, F, F)> = std::iter::successors(Some(point), |&p| Some(frobenius(p))).take(k).collect();
+      assert!(frobenius(orbit[k - 1]) == point, "orbit is not of size {}", k);
+      for conjugate in orbit {
+        assert!(seen.insert(key(conjugate)), "two points of the same orbit over F_{}^{}", q, k);
+      }
+      yielded += 1;
+    }
+    assert_eq!(yielded, closed_point_count(q, k));
+
+    let all = q.pow(2 * k as u32) + q.pow(k as u32) + 1;
+    assert_eq!(F::iterate_over_points().count() as u64, all);
+    let sum: usize = (1..=k).filter(|j| k.is_multiple_of(*j)).map(|j| j * closed_point_count(q, j)).sum();
+    assert_eq!(sum as u64, all);
+  }
+
+  #[test]
+  fn closed_points() {
+    check_closed_points::<F2_i<1>>(2, 1);
+    check_closed_points::<F2_i<2>>(2, 2);
+    check_closed_points::<F2_i<3>>(2, 3);
+    check_closed_points::<F2_i<4>>(2, 4);
+    check_closed_points::<F2_i<6>>(2, 6);
+    check_closed_points::<F2_i<4>>(4, 2);
+    check_closed_points::<F2_i<6>>(4, 3);
+    check_closed_points::<F3_i<2>>(3, 2);
+    check_closed_points::<F3_i<3>>(3, 3);
+    check_closed_points::<F3_i<4>>(9, 2);
+    check_closed_points::<F5_i<2>>(5, 2);
+    // 4 + 2 + 1 over F2, (64 + 8 + 1 - 7) / 3 over F8
+    assert_eq!(closed_point_count(2, 1), 7);
+    assert_eq!(closed_point_count(2, 3), 22);
+  }
+}
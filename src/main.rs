@@ -1,6 +1,11 @@
-use std::{time::Instant, sync::{mpsc, Arc, Mutex}, thread, fs};
+use std::{collections::HashSet, time::Instant, sync::{mpsc, Arc}, fs, env, process};
 
-use algebraic_types::{IsoPolynomial, Lookup, PolynomialResult};
+use algebraic_types::{first_singular_histogram, IsoPolynomial, PolynomialResult};
+use config::{Config, USAGE};
+use extension_ladder::{ExtensionLadder, LadderFamily};
+use journal::{ChunkRecord, Journal};
+use scheduler::Scheduler;
+use field_extensions::{F2, F3, F4, F5, F7, F8, F9, FieldFamily, FieldTraits};
 
 use crate::polynomials::Polynomial;
 
@@ -16,44 +21,28 @@ mod polynomials;
 #[allow(non_snake_case)]
 #[allow(non_camel_case_types)]
 mod field_extensions;
-
-const DEGREE: usize = 3;
-const FIELD_ORDER: usize = 3;
+mod config;
+mod extension_ladder;
+mod matrix;
+mod automorphisms;
+mod validation;
+mod zeta;
+mod groebner;
+mod singular_points;
+mod output;
+mod parser;
+mod journal;
+mod distributed;
+mod scheduler;
+mod checksum;
+mod lookup_cache;
+mod lookup_table;
+mod orbits;
 
 
 const FIELD_EXT_LUT: [usize; 7] = [1,1,2,3,4,6,10];
-const MAX_FIELD_EXT: usize = FIELD_EXT_LUT[DEGREE];
-
-
-#[allow(dead_code)]
-const COEFF_BIT_SIZES: [usize; 5] = [1,1,1,2,2];
-#[allow(dead_code)]
-const COEFF_BIT_SIZE: usize = COEFF_BIT_SIZES[FIELD_ORDER];
-
-const PGL3_SIZES: [f64; 4] = [0., 1., 168., 5616.];
-const PGL3_SIZE: f64 = PGL3_SIZES[FIELD_ORDER];
-
-
-// Q^21 - 1 / 2
-#[allow(dead_code)]
-const POLYNOMIALS: usize = (FIELD_ORDER.pow(21) - 1) / 2;
-const DPLUS2_CHOOSE_2: usize = ((DEGREE+2) * (DEGREE+1)) / 2;
 
 
-const NUM_THREADS: usize = 16;
-const CHUNK_SIZE: usize = 1024;
-const PRINTING: bool = true;
-
-const FILE_NAME: &str = "./output.txt";
-
-// CHANGE THIS:
-type SuperType = (Lookup<1>,Lookup<2>,Lookup<3>,
-                   Lookup<4>,
-                  Lookup<5>,Lookup<6>,
-                  // Lookup<7>,Lookup<8>, Lookup<9>,Lookup<10>,
-                  // Lookup<11>, Lookup<12>
-                  );
-
 #[derive(Debug,Clone,Copy,PartialEq)]
 struct CustomChunk {
   pub start: usize,
@@ -63,65 +52,86 @@ struct CustomChunk {
 
 
 fn main() {
+  let config = match Config::from_args(env::args().skip(1)) {
+    Ok(config) => config,
+    Err(message) => {
+      eprintln!("{}", message);
+      if message != USAGE {
+        eprintln!();
+        eprintln!("{}", USAGE);
+      }
+      process::exit(1);
+    }
+  };
+
+  // Every supported field and polynomial width gets its own monomorphized kernel
+  match config.field_order {
+    2 => run_field::<F2>(&config),
+    3 => run_field::<F3>(&config),
+    4 => run_field::<F4>(&config),
+    5 => run_field::<F5>(&config),
+    7 => run_field::<F7>(&config),
+    8 => run_field::<F8>(&config),
+    9 => run_field::<F9>(&config),
+    _ => unreachable!("Config only accepts supported fields"),
+  }
+}
+
+fn run_field<B: LadderFamily>(config: &Config) {
+  match config.words {
+    1 => run::<B, 1>(config),
+    2 => run::<B, 2>(config),
+    3 => run::<B, 3>(config),
+    4 => run::<B, 4>(config),
+    _ => unreachable!("Config only accepts supported polynomial widths"),
+  }
+}
+
+
+fn run<B: LadderFamily, const W: usize>(config: &Config) {
   let start_time = Instant::now();
 
   
   println!("Generate terms");
-  let normal = Polynomial::generate_default_lut();
-  let (part_x, part_y, part_z) = Polynomial::generate_derative_luts(&normal);
-  
-  let lol = Lookup::<14>::create(&normal, &part_x, &part_y, &part_z);
+  let normal = Polynomial::<W>::generate_default_lut(config.degree);
+  let (part_x, part_y, part_z) = Polynomial::<W>::generate_derative_luts(&normal, <B as FieldFamily>::Base::CHARACTERISTIC);
+
+  if config.generate_orbits {
+    println!("Generating isomorphism classes");
+    let classes = orbits::generate::<<B as FieldFamily>::Base, W>(&normal);
+    orbits::write::<<B as FieldFamily>::Base, W>(&config.input, config.degree, config.field_order, &classes, &normal);
+    println!("Wrote {} isomorphism classes to {}", classes.len(), config.input);
+  }
+
   println!("Importing file");
-  
-  let mut path = format!("input/{}-{}.txt", DEGREE, FIELD_ORDER);
 
-  match fs::metadata(&path) {
-    Ok(_) => {},
-    Err(_) => {
-      println!("File not found, defaulting to 'input.txt'");
-      path = "input.txt".to_owned();
+  let input = fs::read_to_string(&config.input).expect("Unable to open file");
+  let iso_polys = match parser::parse_iso_file::<<B as FieldFamily>::Base, W>(&input, config.degree, config.field_order, &normal) {
+    Ok(iso_polys) => iso_polys,
+    Err(e) => {
+      eprintln!("{}: {}", config.input, e);
+      process::exit(1);
     }
-  }
+  };
+  
+  println!("amount of non-iso polynomials: {}", &iso_polys.iter().fold(0, |acc, iso| acc + iso.size));
 
-  let input = fs::read_to_string(path).expect("Unable to open file");
-  let mut lines = input.lines();
-  // Verifying file validity against program 
-  {
-    lines.next();
-    lines.next();
-    let degree_field_order = lines.next().expect("Incorrect format. Expected the following: \n#Blablabla \n# Homogenous Degree | Field Order \n5 | 3 \n# Constant_(xpower)(ypower)(zpower) ... Constant_(xpower)(ypower)(zpower)  | Isomorphism Class size \n1_023 2_500 | 4 ..."); 
-    
-    let splits: Vec<&str> = degree_field_order.split("|").collect::<Vec<&str>>();
-    let degree = splits[0].split_ascii_whitespace().next().unwrap().parse::<usize>().unwrap();
-    let field_order = splits[1].split_ascii_whitespace().next().unwrap().parse::<usize>().unwrap();
-    lines.next();
-    
-    if degree != DEGREE {
-      panic!("Degree of input file not equal to compiled program")
+  if config.validate {
+    let report = validation::validate::<<B as FieldFamily>::Base, W>(&iso_polys, &normal);
+    report.print::<<B as FieldFamily>::Base, W>(&iso_polys, &normal);
+    if !report.is_valid() {
+      eprintln!("{}: isomorphism class file does not cover every polynomial exactly once", config.input);
+      process::exit(1);
     }
+  }
 
-    if field_order != FIELD_ORDER {
-      panic!("Field order of input file not equal to compiled program")
+  if config.check_stabilizers {
+    println!("Computing stabilizers");
+    if let Err(message) = automorphisms::check_sizes::<<B as FieldFamily>::Base, W>(&iso_polys, &normal, config.printing) {
+      eprintln!("{}: {}", config.input, message);
+      process::exit(1);
     }
   }
-
-  let iso_polys = {
-    let mut iso_polys = vec![];
-
-    for l in lines {
-      let mut line_iter = l.split("|");
-      let polynomial = line_iter.next().unwrap();
-      let iso_size = line_iter.next().unwrap().split_ascii_whitespace().next().unwrap().parse::<u32>().unwrap();
-
-      iso_polys.push(IsoPolynomial {
-          representative: Polynomial::from_string(polynomial, &normal),
-          size: iso_size,
-      });
-    } 
-    iso_polys
-  };
-  
-  println!("amount of non-iso polynomials: {}", &iso_polys.iter().fold(0, |acc, iso| acc + iso.size));
   
   let import_time = Instant::now();
   println!("Generating took: {:?}", (import_time-start_time));
@@ -130,115 +140,222 @@ fn main() {
   // return;
 
   // Generating Lookup Tables
-  // CHANGE THIS: 
-  println!("Generating Lookup tables");
-  let super_lookup: SuperType = ( Lookup::<1>::create(&normal, &part_x, &part_y, &part_z),
-                                  Lookup::<2>::create(&normal, &part_x, &part_y, &part_z),
-                                  Lookup::<3>::create(&normal, &part_x, &part_y, &part_z),
-                                  Lookup::<4>::create(&normal, &part_x, &part_y, &part_z),
-                                  Lookup::<5>::create(&normal, &part_x, &part_y, &part_z),
-                                  Lookup::<6>::create(&normal, &part_x, &part_y, &part_z),
-                                  // Lookup::<7>::create(&normal, &part_x, &part_y, &part_z),
-                                  // Lookup::<8>::create(&normal, &part_x, &part_y, &part_z),
-                                  // Lookup::<9>::create(&normal, &part_x, &part_y, &part_z),
-                                  // Lookup::<10>::create(&normal, &part_x, &part_y, &part_z),
-                                                                  );
+  // A coordinator does not count itself, it only needs the tables for the singular points after counting
+  let create_ladder = || {
+    println!("Generating Lookup tables");
+    Arc::new(ExtensionLadder::<W>::create::<B>(config, &normal, &part_x, &part_y, &part_z))
+  };
+  let arc_ladder = config.coordinator.is_none().then(create_ladder);
 
   let lookup_time = Instant::now();
   println!("Generating took: {:?}", (lookup_time-start_time));
   println!();
 
-  //
-  // Chunk generation so threads get fed evenly
-  //
-  println!("Generate chunks, start threads and count smooth polynomials!");
-  
-  let mut chunks = Vec::new();
-  let mut start = 0;
-  while start < iso_polys.len() {
-    chunks.push(CustomChunk {
-        start,
-        end: std::cmp::min(start + CHUNK_SIZE, iso_polys.len()), // end is exclusive
-    });
-    start += CHUNK_SIZE;
+  let identity = config.run_identity(&input, iso_polys.len());
+  if let Some(address) = &config.worker {
+    let ladder = arc_ladder.expect("Workers build the lookup tables");
+    if let Err(e) = distributed::work(address, &identity, Arc::new(iso_polys), ladder, config.num_threads) {
+      eprintln!("{}", e);
+      process::exit(1);
+    }
+    return;
+  }
+
+  println!("Start threads and count smooth polynomials!");
+
+  // Chunks finished by an earlier run are taken from the journal
+  let header = Journal::header(config, &input, iso_polys.len());
+  let (mut journal, records) = if config.resume {
+    match Journal::resume(&config.journal, &header) {
+      Ok(resumed) => resumed,
+      Err(message) => {
+        eprintln!("{}", message);
+        process::exit(1);
+      }
+    }
+  } else {
+    (Journal::create(&config.journal, &header), vec![])
+  };
+
+  let mut smooth = vec![0; config.enumerated_ext];
+  let mut chunk_results = Vec::new();
+  let mut done = vec![false; iso_polys.len()];
+  for record in records {
+    let valid = record.end() <= iso_polys.len() && !done[record.start..record.end()].contains(&true) && record.matches_extensions(config.enumerated_ext);
+    if !valid {
+      eprintln!("Journal {} does not match this run, the chunk starting at class {} is invalid", config.journal, record.start);
+      process::exit(1);
+    }
+    done[record.start..record.end()].fill(true);
+    for (total, c) in smooth.iter_mut().zip(&record.count) {
+      *total += c;
+    }
+    chunk_results.push((record.start, record.results(&iso_polys)));
   }
-  let chunk_length = chunks.len();
 
-  println!("Amount of Isomorphic polynomials: {} | Amount of chunks: {} | Amount of threads: {}",iso_polys.len(), chunks.len(), NUM_THREADS);
+  // The classes still to count, as ranges of consecutive classes
+  let mut remaining = Vec::new();
+  for i in (0..done.len()).filter(|&i| !done[i]) {
+    match remaining.last_mut() {
+      Some(CustomChunk { end, .. }) if *end == i => *end += 1,
+      _ => remaining.push(CustomChunk { start: i, end: i + 1 }),
+    }
+  }
+  let classes_left: usize = remaining.iter().map(|c| c.end - c.start).sum();
+  if config.resume {
+    println!("Resuming: {} of {} classes were already done", iso_polys.len() - classes_left, iso_polys.len());
+  }
+
+  println!("Amount of Isomorphic polynomials: {} | Amount of threads: {}",iso_polys.len(), config.num_threads);
   println!();
                        
   // Thread arc stuff
   let (tx, rx) = mpsc::channel();
 
-  let arc_super_lookup = Arc::new(super_lookup);
   let arc_iso_polys = Arc::new(iso_polys);
-  let arc_chunks = Arc::new(Mutex::new(chunks));
-  
-  for _ in 0..NUM_THREADS {
-    // Clone the sender to move into each thread
-    let a_tx = tx.clone();
-
-    // Clone the recomputed results to move into each thread locally
-    let local_super_lookup = arc_super_lookup.clone();
-    let local_iso_polys = arc_iso_polys.clone();
-    let local_chunks = arc_chunks.clone();
-
-    // Spawn a new thread
-    thread::spawn(move || {
-      // let lol = &local_chunks.lock().unwrap().pop();
-      loop {
-        let (start,end, index);
-        {
-          let chunk_vec = &mut local_chunks.lock().unwrap();
-          let chunk = chunk_vec.pop();
-          match chunk {
-            Some(t) => { start = t.start; end = t.end; index = chunk_vec.len()}
-            None => {return;}
-          }
-        }
-        
-        let result =  
-        is_smooth(&local_iso_polys, start, end, &local_super_lookup);
-        if PRINTING {
-          println!("Chunks left: {index} | Total Chunks: {chunk_length} | Estimated time: {:.2}", index as f64 * (Instant::now() - lookup_time).as_secs_f64() / (chunk_length - index) as f64);
-        }
-        a_tx.send(result).unwrap();      
-      }
-    });
+  let coordinator = if let Some(address) = &config.coordinator {
+    // Workers get chunks of a fixed size
+    let chunks: Vec<CustomChunk> = remaining.iter()
+      .flat_map(|r| (r.start..r.end).step_by(config.chunk_size).map(|start| CustomChunk { start, end: r.end.min(start + config.chunk_size) }))
+      .rev()
+      .collect();
+    Some(distributed::serve(address, identity, config.enumerated_ext, chunks, arc_iso_polys.clone(), tx))
+  } else {
+    let scheduler = Scheduler::new(&remaining, config.num_threads);
+    scheduler::spawn(scheduler, arc_iso_polys.clone(), arc_ladder.clone().expect("Local runs build the lookup tables"), tx);
+    None
+  };
+
+  // Workers of a coordinator can connect at any time, so we wait for the classes instead of for every sender
+  let mut left = classes_left;
+  while left > 0 {
+    let (start, (count, result)) = rx.recv().expect("Counting threads stopped early");
+    journal.append(&ChunkRecord::new(start, count.clone(), &result));
+    for (total, c) in smooth.iter_mut().zip(count) {
+      *total += c;
+    }
+    left -= result.len();
+    if config.printing {
+      let elapsed = lookup_time.elapsed().as_secs_f64();
+      println!("Classes left: {left} | Total classes: {classes_left} | Estimated time: {:.2}", left as f64 * elapsed / (classes_left - left) as f64);
+    }
+    chunk_results.push((start, result));
+  }
+  if let Some(coordinator) = coordinator {
+    coordinator.finish();
   }
 
-  drop(tx);
+  // Chunks finish in any order, the output follows the input file
+  chunk_results.sort_by_key(|(start, _)| *start);
+  let results: Vec<PolynomialResult<W>> = chunk_results.into_iter().flat_map(|(_, result)| result).collect();
 
-  let mut smooth: [usize; MAX_FIELD_EXT] = [0; MAX_FIELD_EXT];
-  let mut results = Vec::new();
-  for (count, mut result) in rx {
-    for i in 0..MAX_FIELD_EXT {
-      smooth[i] += count[i];
+  // Singular classes stopped climbing the ladder at the first extension with a singular point
+  let (mut results, singular): (Vec<PolynomialResult<W>>, Vec<PolynomialResult<W>>) =
+    results.into_iter().partition(|r| r.points_on_curve.len() == config.enumerated_ext);
+  let first_singular = first_singular_histogram(&singular, config.enumerated_ext);
+  if let Some(path) = &config.singular_output {
+    output::write_singular::<<B as FieldFamily>::Base, W>(path, &singular, &normal);
+  }
+
+  // The zeta function of every smooth curve, checked against all point counts we have
+  let genus = zeta::genus(config.degree);
+  let mut failed_checks = 0;
+  for result in results.iter_mut() {
+    result.compute_l_polynomial::<<B as FieldFamily>::Base>(genus);
+    let check = match &result.l_polynomial {
+      Some(l_polynomial) => l_polynomial.check(&result.points_on_curve),
+      None if result.points_on_curve.len() >= genus => Err("point counts do not give an integral L-polynomial".to_owned()),
+      None => Ok(()),
+    };
+    let extended = result.extend_points_on_curve(config.count_ext);
+    if let Err(message) = check.and(extended) {
+      println!("Zeta function check failed for {}: {}", result.poly.representative.str::<<B as FieldFamily>::Base>(&normal), message);
+      failed_checks += 1;
     }
-    results.append(&mut result);
   }
-  
 
-  let a: Vec<String> = results.iter().map(|t| t.to_string(&normal)).collect();
-  let b = a.join("\n");
-  let c = "# Smooth polynomial representative (CONSTANT_(xpower)(ypower)(zpower)) | isomoprhism class | points defined over k_i\n".to_owned() + &b;
-  fs::write(FILE_NAME, c).expect("Unable to write file");
+  if config.verify_exact {
+    println!("Verifying smoothness with Groebner bases");
+    let smooth_representatives: HashSet<[u64; W]> = results.iter().map(|r| r.poly.representative.bits).collect();
+    let mut mismatches = 0;
+    for iso_poly in arc_iso_polys.iter() {
+      let certificate = iso_poly.representative.is_smooth_exact::<<B as FieldFamily>::Base>(&normal);
+      if certificate.is_smooth() != smooth_representatives.contains(&iso_poly.representative.bits) {
+        println!("Point search and exact test disagree on {}: {}", iso_poly.representative.str::<<B as FieldFamily>::Base>(&normal), certificate.str());
+        mismatches += 1;
+      }
+    }
+    if mismatches == 0 {
+      println!("Exact test agrees with the point search on all {} representatives", arc_iso_polys.len());
+    } else {
+      println!("Exact test disagrees with the point search on {} representatives", mismatches);
+    }
+  }
+
+  if let Some(path) = &config.singular_points {
+    let smooth_representatives: HashSet<[u64; W]> = results.iter().map(|r| r.poly.representative.bits).collect();
+    let mut lines = vec!["# Singular polynomial representative | isomorphism class | point over k_i | degree | multiplicity | type | tangent cone".to_owned()];
+    let ladder = arc_ladder.clone().unwrap_or_else(create_ladder);
+    for iso_poly in arc_iso_polys.iter().filter(|iso| !smooth_representatives.contains(&iso.representative.bits)) {
+      let representative = iso_poly.representative.str::<<B as FieldFamily>::Base>(&normal);
+      for point in ladder.singular_points(iso_poly.representative, &normal) {
+        lines.push(format!("{} | {} | {}", representative, iso_poly.size, point.str()));
+      }
+    }
+    fs::write(path, lines.join("\n") + "\n").expect("Unable to write file");
+    println!("Wrote singular points to {}", path);
+  }
+
+  // Same for the same results, whatever the order of the input file or the scheduling
+  let digest = checksum::digest(&results.iter().map(|r| r.to_string::<<B as FieldFamily>::Base>(&normal)).collect::<Vec<String>>());
+
+  let metadata = output::Metadata {
+    degree: config.degree,
+    field_order: config.field_order,
+    max_field_ext: config.max_field_ext,
+    count_ext: config.count_ext,
+    runtime: start_time.elapsed(),
+    digest,
+  };
+  output::write_results::<<B as FieldFamily>::Base, W>(&config.output, config.format, &metadata, &results, &normal);
+  // Everything in the journal is in the output now
+  drop(journal);
+  fs::remove_file(&config.journal).expect("Unable to remove journal");
   
 
-  for i in 0..MAX_FIELD_EXT {
-    println!("{}: {}", i+1, smooth[i]);
+  for (i, s) in smooth.iter().enumerate() {
+    println!("{}: {}", i+1, s);
+  }
+  println!();
+  println!("First k_i with a singular point, weighted by class size:");
+  for (i, s) in first_singular.iter().enumerate() {
+    println!("{}: {}", i+1, s);
+  }
+  println!("Smooth over k_1 but singular over an extension: {}", first_singular.iter().skip(1).sum::<usize>());
+  println!("Amount of singular isomorphism classes: {}", singular.len());
+  if config.count_ext > config.enumerated_ext {
+    println!("Point counts over k_{} up to k_{} are predicted by the L-polynomials", config.enumerated_ext + 1, config.count_ext);
   }
   println!();
   println!("Amount of isomorphism classes: {}",results.len());
-  let frequency = results.iter().fold(0, |acc, t| acc + t.poly.size) as f64 / PGL3_SIZE;
+  println!("Digest of the results: {:016x}", digest);
+  let pgl3_size = config.pgl3_size();
+  let frequency = results.iter().fold(0, |acc, t| acc + t.poly.size) as f64 / pgl3_size;
   println!("Frequency: {}", frequency);
-  println!("????: {}", (smooth[MAX_FIELD_EXT-1] as f64 / PGL3_SIZE));
-  if frequency == (smooth[MAX_FIELD_EXT-1] as f64 / PGL3_SIZE) {
+  println!("Frequency from the polynomials smooth over k_1 up to k_{}: {}", config.enumerated_ext, smooth[config.enumerated_ext-1] as f64 / pgl3_size);
+  if frequency == (smooth[config.enumerated_ext-1] as f64 / pgl3_size) {
     println!("Frequency is correct!");
   } else {
-    println!("Frequency does not match! Should be: {}", smooth[MAX_FIELD_EXT-1] as f64 / PGL3_SIZE);
+    println!("Frequency does not match! Should be: {}", smooth[config.enumerated_ext-1] as f64 / pgl3_size);
+  }
+  if genus > config.enumerated_ext {
+    println!("L-polynomials need point counts up to k = {}, raise --max-ext or --count-ext to get them", genus);
+  } else if failed_checks == 0 {
+    println!("All L-polynomials satisfy the functional equation and the Weil bounds");
+  } else {
+    println!("{} L-polynomials failed their checks", failed_checks);
   }
-  println!("Polynomials had Degree: {}",  DEGREE);
+  println!("Polynomials had Degree: {}",  config.degree);
   println!("Total time: {:?}", start_time.elapsed());
 }
 
@@ -246,79 +363,16 @@ fn main() {
 
 
 
-fn is_smooth(iso_polys: &Vec<IsoPolynomial>, start: usize, end: usize, super_lut: &SuperType) -> ([usize; MAX_FIELD_EXT], Vec<PolynomialResult>) {
-  let mut count: [usize; MAX_FIELD_EXT] = [0; MAX_FIELD_EXT];
-  let mut results: Vec<PolynomialResult> = Vec::new();
-  for i in start..end {
-    if i >= iso_polys.len() {break;}
-    let iso_poly = &iso_polys[i];
-    let (poly, size) = iso_poly.deconstruct();
-    let mut points_on_curve = [0; MAX_FIELD_EXT];
-
-
-    // CHANGE THIS: 
-    let result = poly.has_singularity(&super_lut.0);
-    if result == None {continue;}
-    count[0] += size as usize;
-    points_on_curve[0] += result.unwrap();
-
-    let result = poly.has_singularity(&super_lut.1);
-    if result == None {continue;}
-    count[1] += size as usize;
-    points_on_curve[1] += result.unwrap();
-
-    let result = poly.has_singularity(&super_lut.2);
-    if result == None {continue;}
-    count[2] += size as usize;
-    points_on_curve[2] += result.unwrap();
-
-    // let result = poly.has_singularity(&super_lut.3);
-    // if result == None {continue;}
-    // count[3] += size as usize;
-    // points_on_curve[3] += result.unwrap();
-
-    // let result = poly.has_singularity(&super_lut.4);
-    // if result == None {continue;}
-    // count[4] += size as usize;
-    // points_on_curve[4] += result.unwrap();
-
-    // let result = poly.has_singularity(&super_lut.5);
-    // if result == None {continue;}
-    // count[5] += size as usize;
-    // points_on_curve[5] += result.unwrap();
-
-    // let result = poly.has_singularity(&super_lut.6);
-    // if result == None {continue;}
-    // count[6] += size as usize;
-    // points_on_curve[6] += result.unwrap();
-
-    // let result = poly.has_singularity(&super_lut.7);
-    // if result == None {continue;}
-    // count[7] += size as usize;
-    // points_on_curve[7] += result.unwrap();
-
-    // let result = poly.has_singularity(&super_lut.8);
-    // if result == None {continue;}
-    // count[8] += size as usize;
-    // points_on_curve[8] += result.unwrap();
-
-    // let result = poly.has_singularity(&super_lut.9);
-    // if result == None {continue;}
-    // count[9] += size as usize;
-    // points_on_curve[9] += result.unwrap();
-
-    // let result = poly.has_singularity(&super_lut.10);
-    // if result == None {println!("FOUND ONE IN 11!!"); continue;}
-    // count[10] += size as usize;
-    // points_on_curve[10] += result.unwrap();
-
-    // let result = poly.has_singularity(&super_lut.11);
-    // if result == None {continue;}
-    // count[11] += size as usize;
-    // points_on_curve[11] += result.unwrap();
-
-
+fn is_smooth<const W: usize>(iso_polys: &[IsoPolynomial<W>], start: usize, end: usize, ladder: &ExtensionLadder<W>) -> (Vec<usize>, Vec<PolynomialResult<W>>) {
+  let mut count = vec![0; ladder.max_field_ext()];
+  let mut results: Vec<PolynomialResult<W>> = Vec::new();
+  let iso_polys = &iso_polys[start..end.min(iso_polys.len())];
+  let polys: Vec<Polynomial<W>> = iso_polys.iter().map(|iso_poly| iso_poly.representative).collect();
+  for (iso_poly, points_on_curve) in iso_polys.iter().zip(ladder.points_on_curve(&polys)) {
+    for c in count.iter_mut().take(points_on_curve.len()) {
+      *c += iso_poly.size as usize;
+    }
     results.push(PolynomialResult::new(*iso_poly, points_on_curve))
   }
   (count,results)
-}
\ No newline at end of file
+}
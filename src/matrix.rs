@@ -0,0 +1,127 @@
+use std::ops::Mul;
+
+use crate::field_extensions::FieldTraits;
+
+
+//
+//
+// 3x3 matrices over a field, the elements of GL3 and (up to scalars) PGL3
+// A matrix M acts on a polynomial by linear substitution, see Polynomial::substitute
+//
+//
+
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Matrix3<F: FieldTraits> {
+  pub rows: [[F; 3]; 3],
+}
+
+impl<F: FieldTraits> Matrix3<F> {
+  pub fn new(rows: [[F; 3]; 3]) -> Matrix3<F> {
+    Matrix3 { rows }
+  }
+
+  pub fn identity() -> Matrix3<F> {
+    let mut rows = [[F::ZERO; 3]; 3];
+    for (i, row) in rows.iter_mut().enumerate() {
+      row[i] = F::ONE;
+    }
+    Matrix3 { rows }
+  }
+
+  // The 2x2 minor left after removing row i and column j
+  fn minor(&self, i: usize, j: usize) -> F {
+    let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+    let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+    self.rows[r0][c0] * self.rows[r1][c1] + (self.rows[r0][c1] * self.rows[r1][c0]).neg()
+  }
+
+  // Cyclic indices in minor take care of the signs of the cofactors
+  pub fn determinant(&self) -> F {
+    (0..3).fold(F::ZERO, |acc, j| acc + self.rows[0][j] * self.minor(0, j))
+  }
+
+  pub fn is_invertible(&self) -> bool {
+    self.determinant() != F::ZERO
+  }
+
+  // The adjugate divided by the determinant
+  pub fn inverse(&self) -> Option<Matrix3<F>> {
+    let det = self.determinant();
+    if det == F::ZERO {
+      return None
+    }
+    let inv = det.inverse();
+    let mut rows = [[F::ZERO; 3]; 3];
+    for (i, row) in rows.iter_mut().enumerate() {
+      for (j, entry) in row.iter_mut().enumerate() {
+        *entry = self.minor(j, i) * inv;
+      }
+    }
+    Some(Matrix3 { rows })
+  }
+
+  // Multiplies every entry by the same scalar, which gives the same element of PGL3
+  pub fn scale(&self, scalar: F) -> Matrix3<F> {
+    Matrix3 { rows: self.rows.map(|row| row.map(|e| e * scalar)) }
+  }
+
+  // The representative of the class in PGL3 whose first nonzero entry is one
+  pub fn normalize(&self) -> Matrix3<F> {
+    match self.rows.iter().flatten().find(|&&e| e != F::ZERO) {
+      Some(&first) => self.scale(first.inverse()),
+      None => *self,
+    }
+  }
+}
+
+impl<F: FieldTraits> Mul for Matrix3<F> {
+  type Output = Matrix3<F>;
+
+  fn mul(self, rhs: Self) -> Self::Output {
+    let mut rows = [[F::ZERO; 3]; 3];
+    for (i, row) in rows.iter_mut().enumerate() {
+      for (j, entry) in row.iter_mut().enumerate() {
+        *entry = (0..3).fold(F::ZERO, |acc, k| acc + self.rows[i][k] * rhs.rows[k][j]);
+      }
+    }
+    Matrix3 { rows }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::field_extensions::{F2_i, F3_i, F7_i};
+
+  // A matrix given by the bits of its entries
+  fn matrix<F: FieldTraits>(rows: [[u64; 3]; 3]) -> Matrix3<F> {
+    Matrix3::new(rows.map(|row| row.map(F::from_bits)))
+  }
+
+  fn check_inverse<F: FieldTraits>(m: Matrix3<F>) {
+    let inverse = m.inverse().expect("matrix is invertible");
+    assert!(m * inverse == Matrix3::identity());
+    assert!(inverse * m == Matrix3::identity());
+  }
+
+  #[test]
+  fn inverse_over_prime_fields() {
+    check_inverse(matrix::<F3_i<1>>([[1, 2, 0], [0, 1, 1], [2, 0, 1]]));
+    check_inverse(matrix::<F7_i<1>>([[3, 5, 6], [1, 0, 2], [4, 4, 2]]));
+  }
+
+  #[test]
+  fn inverse_over_extensions() {
+    check_inverse(matrix::<F2_i<2>>([[2, 1, 0], [3, 0, 1], [1, 0, 1]]));
+    check_inverse(matrix::<F3_i<2>>([[1, 0b100, 0], [0b1001, 1, 0b10], [0, 0b110, 1]]));
+  }
+
+  #[test]
+  fn singular_matrix_has_no_inverse() {
+    let m = matrix::<F3_i<1>>([[1, 2, 0], [2, 1, 0], [0, 0, 1]]);
+    assert!(m.determinant() == F3_i::ZERO);
+    assert!(m.inverse().is_none());
+  }
+}
@@ -0,0 +1,266 @@
+use std::{fs, path::Path};
+
+use crate::algebraic_types::IsoPolynomial;
+use crate::field_extensions::FieldTraits;
+use crate::matrix::Matrix3;
+use crate::polynomials::{bits_to_constant, constant_to_bits, substitute_monomials, Polynomial, Term};
+
+
+//
+//
+// Generation of the isomorphism classes of polynomials under PGL3(F_q)
+// A matrix M acts on a polynomial f by linear substitution, f(x, y, z) -> f(M (x, y, z))
+// This is linear in the coefficients of f, so every matrix becomes a matrix on the coefficient space once
+// The orbit walk itself works on the constants through addition and multiplication tables, which is a lot faster
+// Polynomials are written as numbers sum c_i q^i, with c_i the constants as in the input files
+//
+//
+
+
+// Largest polynomial space we are willing to walk through, we keep one bit per polynomial
+const MAX_POLYNOMIALS: u64 = 1 << 34;
+// Largest amount of bytes for the actions of all of PGL3(F_q), n * n constants each
+const MAX_ACTION_BYTES: u64 = 1 << 31;
+// Largest amount of multiply-adds of the orbit walk, at roughly a nanosecond each, validation uses the same limit
+pub const MAX_OPERATIONS: u64 = 1 << 40;
+
+
+// Addition and multiplication tables of F_q, on the constants 0..q of the input files
+pub struct FieldTables {
+  pub q: usize,
+  add: Vec<u8>,
+  mul: Vec<u8>,
+  inv: Vec<u8>,
+}
+
+impl FieldTables {
+  pub fn new<B: FieldTraits>() -> FieldTables {
+    let mut elements = vec![B::ZERO];
+    while let Some(next) = elements[elements.len() - 1].next() {
+      elements.push(next);
+    }
+    let q = elements.len();
+    let constant = |e: B| bits_to_constant::<B>(e.to_bits()) as u8;
+    let element = |c: usize| B::from_bits(constant_to_bits::<B>(c as u64));
+
+    let mut add = vec![0; q * q];
+    let mut mul = vec![0; q * q];
+    let mut inv = vec![0; q];
+    for a in 0..q {
+      for b in 0..q {
+        add[a * q + b] = constant(element(a) + element(b));
+        mul[a * q + b] = constant(element(a) * element(b));
+        if mul[a * q + b] == 1 {
+          inv[a] = b as u8;
+        }
+      }
+    }
+    FieldTables { q, add, mul, inv }
+  }
+
+  pub fn add(&self, a: u8, b: u8) -> u8 {
+    self.add[a as usize * self.q + b as usize]
+  }
+
+  pub fn mul(&self, a: u8, b: u8) -> u8 {
+    self.mul[a as usize * self.q + b as usize]
+  }
+
+  pub fn inv(&self, a: u8) -> u8 {
+    self.inv[a as usize]
+  }
+}
+
+
+// All matrices of PGL3(F_q), scaled such that their first nonzero entry is one
+// Lazy, since for larger q the group no longer comfortably fits in memory
+pub fn pgl3<B: FieldTraits>() -> impl Iterator<Item = Matrix3<B>> {
+  let mut elements = vec![B::ZERO];
+  while let Some(next) = elements[elements.len() - 1].next() {
+    elements.push(next);
+  }
+  let q = elements.len();
+
+  (0..q.pow(9)).filter_map(move |index| {
+    let mut rows = [[B::ZERO; 3]; 3];
+    let mut rest = index;
+    for entry in rows.iter_mut().flatten() {
+      *entry = elements[rest % q];
+      rest /= q;
+    }
+    let m = Matrix3::new(rows);
+    (m.normalize() == m && m.is_invertible()).then_some(m)
+  })
+}
+
+pub fn pgl3_order(q: u64) -> u64 {
+  q.pow(3) * (q.pow(3) - 1) * (q.pow(2) - 1)
+}
+
+
+// The map f -> f(M (x, y, z)) on the coefficients of polynomials of a fixed degree
+// Flat, columns[i * n..(i + 1) * n] is the image of the i-th monomial
+pub struct LinearAction {
+  columns: Vec<u8>,
+}
+
+impl LinearAction {
+  pub fn new<B: FieldTraits>(m: &Matrix3<B>, normal: &[Term]) -> LinearAction {
+    let columns = substitute_monomials(m, normal).into_iter()
+      .flat_map(|image| image.into_iter().map(|c| bits_to_constant::<B>(c.to_bits()) as u8))
+      .collect();
+    LinearAction { columns }
+  }
+
+  pub fn apply(&self, field: &FieldTables, coefficients: &[u8], out: &mut [u8]) {
+    apply_columns(field, &self.columns, coefficients, out)
+  }
+}
+
+fn apply_columns(field: &FieldTables, columns: &[u8], coefficients: &[u8], out: &mut [u8]) {
+  out.fill(0);
+  for (&c, column) in coefficients.iter().zip(columns.chunks_exact(out.len())) {
+    if c == 0 {
+      continue;
+    }
+    for (o, &v) in out.iter_mut().zip(column) {
+      *o = field.add(*o, field.mul(c, v));
+    }
+  }
+}
+
+
+// Scales the polynomial such that its first nonzero coefficient is one
+pub fn normalize(field: &FieldTables, coefficients: &mut [u8]) {
+  if let Some(&first) = coefficients.iter().find(|&&c| c != 0) {
+    let inv = field.inv(first);
+    for c in coefficients.iter_mut() {
+      *c = field.mul(*c, inv);
+    }
+  }
+}
+
+fn to_index(q: usize, coefficients: &[u8]) -> u64 {
+  coefficients.iter().rev().fold(0, |acc, &c| acc * q as u64 + c as u64)
+}
+
+fn from_index(q: usize, mut index: u64, coefficients: &mut [u8]) {
+  for c in coefficients.iter_mut() {
+    *c = (index % q as u64) as u8;
+    index /= q as u64;
+  }
+}
+
+pub fn to_constants<B: FieldTraits, const W: usize>(poly: &Polynomial<W>, n: usize) -> Vec<u8> {
+  (0..n).map(|i| bits_to_constant::<B>(poly.coefficient::<B>(i)) as u8).collect()
+}
+
+pub fn to_polynomial<B: FieldTraits, const W: usize>(coefficients: &[u8]) -> Polynomial<W> {
+  let bits: Vec<u64> = coefficients.iter().map(|&c| constant_to_bits::<B>(c as u64)).collect();
+  Polynomial::from_coefficient_bits::<B>(&bits)
+}
+
+
+pub fn polynomial_space_size(field_order: usize, degree: usize) -> Option<u64> {
+  let n = (degree + 2) * (degree + 1) / 2;
+  (field_order as u64).checked_pow(n as u32).filter(|&size| size <= MAX_POLYNOMIALS)
+}
+
+// Whether generate fits in memory and time, the walk applies every action to at least total / |PGL3| classes
+pub fn check_generation(field_order: usize, degree: usize) -> Result<(), String> {
+  let n = ((degree + 2) * (degree + 1) / 2) as u64;
+  let total = polynomial_space_size(field_order, degree)
+    .ok_or(format!("Too many polynomials of degree {} over F{} to generate the isomorphism classes", degree, field_order))?;
+  let order = pgl3_order(field_order as u64);
+  if order * n * n > MAX_ACTION_BYTES {
+    return Err(format!("The actions of PGL3(F{}) on degree {} take {} MiB, more than the {} MiB generating the classes may use", field_order, degree, (order * n * n) >> 20, MAX_ACTION_BYTES >> 20))
+  }
+  let operations = (total / order + 1).saturating_mul(order * n * n);
+  if operations > MAX_OPERATIONS {
+    return Err(format!("Generating the isomorphism classes of degree {} over F{} takes about {} operations, too many to finish", degree, field_order, operations))
+  }
+  Ok(())
+}
+
+// Walks through all polynomials in increasing order, the first one of every orbit we meet is its smallest element
+// That one becomes the canonical representative, and the orbit gets marked as seen
+pub fn generate<B: FieldTraits, const W: usize>(normal: &[Term]) -> Vec<IsoPolynomial<W>> {
+  let field = FieldTables::new::<B>();
+  let q = field.q;
+  let n = normal.len();
+  let degree = normal[0].x_deg as usize + normal[0].y_deg as usize + normal[0].z_deg as usize;
+  let total = polynomial_space_size(q, degree).expect("Polynomial space too large to generate isomorphism classes");
+
+  // One flat array, a Vec per action costs more in allocations than in constants
+  let mut actions = Vec::with_capacity(pgl3_order(q as u64) as usize * n * n);
+  for m in pgl3::<B>() {
+    actions.extend(LinearAction::new(&m, normal).columns);
+  }
+  println!("PGL3(F{}) has {} elements", q, actions.len() / (n * n));
+
+  let mut seen = vec![0u64; total.div_ceil(64) as usize];
+  let mut classes = vec![];
+  let mut coefficients = vec![0; n];
+  let mut image = vec![0; n];
+  for index in 1..total {
+    if seen[(index / 64) as usize] >> (index % 64) & 1 == 1 {
+      continue;
+    }
+    from_index(q, index, &mut coefficients);
+    if coefficients.iter().find(|&&c| c != 0) != Some(&1) {
+      continue;
+    }
+
+    let mut size = 0;
+    for columns in actions.chunks_exact(n * n) {
+      apply_columns(&field, columns, &coefficients, &mut image);
+      normalize(&field, &mut image);
+      let image_index = to_index(q, &image);
+      if seen[(image_index / 64) as usize] >> (image_index % 64) & 1 == 0 {
+        seen[(image_index / 64) as usize] |= 1 << (image_index % 64);
+        size += 1;
+      }
+    }
+    classes.push(IsoPolynomial { representative: to_polynomial::<B, W>(&coefficients), size });
+  }
+  classes
+}
+
+// The smallest element of the orbit of every polynomial, as constants
+// Two polynomials are isomorphic exactly when their canonical forms agree
+pub fn canonical_forms<B: FieldTraits, const W: usize>(polys: &[Polynomial<W>], normal: &[Term]) -> Vec<Vec<u8>> {
+  let field = FieldTables::new::<B>();
+  let n = normal.len();
+  let polys: Vec<Vec<u8>> = polys.iter().map(|poly| to_constants::<B, W>(poly, n)).collect();
+
+  // Comparing the reversed constants is comparing the numbers sum c_i q^i, without them having to fit in a u64
+  let mut canonical: Vec<Vec<u8>> = polys.iter().map(|_| vec![u8::MAX; n]).collect();
+  let mut image = vec![0; n];
+  for m in pgl3::<B>() {
+    let action = LinearAction::new(&m, normal);
+    for (poly, canonical) in polys.iter().zip(canonical.iter_mut()) {
+      action.apply(&field, poly, &mut image);
+      normalize(&field, &mut image);
+      if image.iter().rev().lt(canonical.iter().rev()) {
+        canonical.copy_from_slice(&image);
+      }
+    }
+  }
+  canonical
+}
+
+// Writes the classes in the same format as the input files of the main program
+pub fn write<B: FieldTraits, const W: usize>(path: &str, degree: usize, field_order: usize, classes: &[IsoPolynomial<W>], normal: &[Term]) {
+  let mut lines = vec![
+    "# Isomorphism classes under PGL3, generated by affiene_variety".to_owned(),
+    "# Homogenous Degree | Field Order".to_owned(),
+    format!("{} | {}", degree, field_order),
+    "# Constant_(xpower)(ypower)(zpower) ... Constant_(xpower)(ypower)(zpower) | Isomorphism Class size".to_owned(),
+  ];
+  lines.extend(classes.iter().map(|c| format!("{} | {}", c.representative.str::<B>(normal), c.size)));
+
+  if let Some(dir) = Path::new(path).parent() {
+    fs::create_dir_all(dir).expect("Unable to create directory");
+  }
+  fs::write(path, lines.join("\n") + "\n").expect("Unable to write file");
+}
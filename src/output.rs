@@ -0,0 +1,430 @@
+use std::{fs, time::Duration};
+
+use crate::algebraic_types::PolynomialResult;
+use crate::field_extensions::FieldTraits;
+use crate::polynomials::{bits_to_constant, Term};
+
+
+//
+//
+// Writers for the smooth polynomials, the original text format and machine readable CSV, JSON and JSON lines
+// Every format carries the same fields: the representative, its coefficients in the order of the monomials,
+// the size of its isomorphism class, its points over every k_i and its L-polynomial when that is known
+//
+//
+
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+  Text,
+  Csv,
+  Json,
+  Jsonl,
+}
+
+impl OutputFormat {
+  pub fn parse(format: &str) -> Option<OutputFormat> {
+    match format {
+      "text" => Some(OutputFormat::Text),
+      "csv" => Some(OutputFormat::Csv),
+      "json" => Some(OutputFormat::Json),
+      "jsonl" => Some(OutputFormat::Jsonl),
+      _ => None,
+    }
+  }
+}
+
+
+pub struct Metadata {
+  pub degree: usize,
+  pub field_order: usize,
+  pub max_field_ext: usize,
+  pub count_ext: usize,
+  pub runtime: Duration,
+  // See checksum::digest
+  pub digest: u64,
+}
+
+impl Metadata {
+  fn fields(&self, normal: &[Term]) -> Vec<(&'static str, String)> {
+    let monomials: Vec<String> = normal.iter().map(|t| json_string(&t.str())).collect();
+    vec![
+      ("version", json_string(env!("CARGO_PKG_VERSION"))),
+      ("degree", self.degree.to_string()),
+      ("field_order", self.field_order.to_string()),
+      ("max_field_ext", self.max_field_ext.to_string()),
+      ("count_ext", self.count_ext.to_string()),
+      ("runtime_seconds", self.runtime.as_secs_f64().to_string()),
+      ("digest", json_string(&format!("{:016x}", self.digest))),
+      ("monomials", format!("[{}]", monomials.join(","))),
+    ]
+  }
+}
+
+
+fn json_string(s: &str) -> String {
+  let mut res = String::from("\"");
+  for c in s.chars() {
+    match c {
+      '"' => res.push_str("\\\""),
+      '\\' => res.push_str("\\\\"),
+      c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+      c => res.push(c),
+    }
+  }
+  res.push('"');
+  res
+}
+
+fn json_object(fields: &[(&str, String)]) -> String {
+  let fields: Vec<String> = fields.iter().map(|(key, value)| format!("{}:{}", json_string(key), value)).collect();
+  format!("{{{}}}", fields.join(","))
+}
+
+// RFC 4180, a field with a comma, quote or line break is quoted and its quotes are doubled
+fn csv_field(s: &str) -> String {
+  if s.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", s.replace('"', "\"\""))
+  } else {
+    s.to_owned()
+  }
+}
+
+fn join<T: ToString>(values: &[T], separator: &str) -> String {
+  values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(separator)
+}
+
+
+struct Row {
+  representative: String,
+  coefficients: Vec<u64>,
+  size: u32,
+  points_on_curve: Vec<usize>,
+  l_polynomial: Option<Vec<i128>>,
+}
+
+impl Row {
+  fn new<B: FieldTraits, const W: usize>(result: &PolynomialResult<W>, normal: &[Term]) -> Row {
+    let poly = result.poly.representative;
+    Row {
+      representative: poly.str::<B>(normal),
+      coefficients: (0..normal.len()).map(|i| bits_to_constant::<B>(poly.coefficient::<B>(i))).collect(),
+      size: result.poly.size,
+      points_on_curve: result.points_on_curve.clone(),
+      l_polynomial: result.l_polynomial.as_ref().map(|l| l.coefficients.clone()),
+    }
+  }
+
+  fn json(&self) -> String {
+    json_object(&[
+      ("representative", json_string(&self.representative)),
+      ("coefficients", format!("[{}]", join(&self.coefficients, ","))),
+      ("size", self.size.to_string()),
+      ("points_on_curve", format!("[{}]", join(&self.points_on_curve, ","))),
+      ("l_polynomial", self.l_polynomial.as_ref().map_or("null".to_owned(), |l| format!("[{}]", join(l, ",")))),
+    ])
+  }
+
+  // Lists are separated by semicolons so they stay a single field, the representative is quoted
+  // when it needs to be, exponents of 10 or more are written as [a,b,c]
+  fn csv(&self) -> String {
+    let l_polynomial = self.l_polynomial.as_ref().map_or(String::new(), |l| join(l, ";"));
+    format!("{},{},{},{},{}", csv_field(&self.representative), join(&self.coefficients, ";"), self.size, join(&self.points_on_curve, ";"), l_polynomial)
+  }
+}
+
+
+pub fn write_results<B: FieldTraits, const W: usize>(path: &str, format: OutputFormat, metadata: &Metadata, results: &[PolynomialResult<W>], normal: &[Term]) {
+  let contents = match format {
+    OutputFormat::Text => {
+      let lines: Vec<String> = results.iter().map(|t| t.to_string::<B>(normal)).collect();
+      "# Smooth polynomial representative (CONSTANT_(xpower)(ypower)(zpower)) | isomoprhism class | points defined over k_i | L-polynomial\n".to_owned() + &lines.join("\n")
+    },
+    OutputFormat::Csv => {
+      let mut lines: Vec<String> = metadata.fields(normal).into_iter().map(|(key, value)| format!("# {}: {}", key, value.replace('"', ""))).collect();
+      lines.push("representative,coefficients,size,points_on_curve,l_polynomial".to_owned());
+      lines.extend(results.iter().map(|r| Row::new::<B, W>(r, normal).csv()));
+      lines.join("\n") + "\n"
+    },
+    OutputFormat::Json => {
+      let rows: Vec<String> = results.iter().map(|r| Row::new::<B, W>(r, normal).json()).collect();
+      json_object(&[("metadata", json_object(&metadata.fields(normal))), ("results", format!("[\n{}\n]", rows.join(",\n")))]) + "\n"
+    },
+    OutputFormat::Jsonl => {
+      let mut lines = vec![json_object(&[("metadata", json_object(&metadata.fields(normal)))])];
+      lines.extend(results.iter().map(|r| Row::new::<B, W>(r, normal).json()));
+      lines.join("\n") + "\n"
+    },
+  };
+  fs::write(path, contents).expect("Unable to write file");
+}
+
+// The singular classes with the first extension they are singular over, in the text format
+pub fn write_singular<B: FieldTraits, const W: usize>(path: &str, singular: &[PolynomialResult<W>], normal: &[Term]) {
+  let mut lines = vec!["# Singular polynomial representative | isomorphism class | first k_i with a singular point | points defined over k_i before that".to_owned()];
+  lines.extend(singular.iter().map(|r| format!("{} | {} | {} | {:?}", r.poly.representative.str::<B>(normal), r.poly.size, r.first_singular_extension(), r.points_on_curve)));
+  fs::write(path, lines.join("\n") + "\n").expect("Unable to write file");
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::algebraic_types::IsoPolynomial;
+  use crate::field_extensions::F2_i;
+  use crate::parser;
+  use crate::polynomials::Polynomial;
+  use crate::zeta::LPolynomial;
+
+  // Just enough JSON to read back what the writers produce
+  #[derive(Debug, Clone, PartialEq)]
+  enum Json {
+    Null,
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+  }
+
+  impl Json {
+    fn parse(s: &str) -> Json {
+      let mut chars = s.trim().chars().peekable();
+      let value = Json::value(&mut chars);
+      assert!(chars.all(char::is_whitespace), "trailing characters after JSON value");
+      value
+    }
+
+    fn value(chars: &mut std::iter::Peekable<std::str::Chars>) -> Json {
+      while chars.next_if(|c| c.is_whitespace()).is_some() {}
+      match chars.next().unwrap() {
+        'n' => {
+          assert_eq!(chars.take(3).collect::<String>(), "ull");
+          Json::Null
+        },
+        '"' => {
+          let mut s = String::new();
+          loop {
+            match chars.next().unwrap() {
+              '"' => return Json::String(s),
+              '\\' => match chars.next().unwrap() {
+                'u' => s.push(char::from_u32(u32::from_str_radix(&chars.take(4).collect::<String>(), 16).unwrap()).unwrap()),
+                c => s.push(c),
+              },
+              c => s.push(c),
+            }
+          }
+        },
+        '[' => Json::Array(Json::list(chars, ']', Json::value)),
+        '{' => Json::Object(Json::list(chars, '}', |chars| {
+          let Json::String(key) = Json::value(chars) else { panic!("object key is not a string") };
+          while chars.next_if(|c| c.is_whitespace()).is_some() {}
+          assert_eq!(chars.next(), Some(':'));
+          (key, Json::value(chars))
+        })),
+        c => {
+          let mut number = c.to_string();
+          while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+            number.push(c);
+          }
+          Json::Number(number)
+        },
+      }
+    }
+
+    fn list<T>(chars: &mut std::iter::Peekable<std::str::Chars>, end: char, mut item: impl FnMut(&mut std::iter::Peekable<std::str::Chars>) -> T) -> Vec<T> {
+      let mut items = vec![];
+      loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        if chars.next_if_eq(&end).is_some() {
+          return items
+        }
+        items.push(item(chars));
+      }
+    }
+
+    fn get(&self, key: &str) -> &Json {
+      let Json::Object(fields) = self else { panic!("not an object") };
+      &fields.iter().find(|(k, _)| k == key).unwrap_or_else(|| panic!("missing key {}", key)).1
+    }
+
+    fn numbers<T: std::str::FromStr>(&self) -> Vec<T> {
+      let Json::Array(values) = self else { panic!("not an array") };
+      values.iter().map(|v| match v {
+        Json::Number(n) => n.parse().ok().unwrap(),
+        _ => panic!("not a number"),
+      }).collect()
+    }
+  }
+
+  // Two smooth cubics over F2 with their L-polynomials, and one whose counts do not reach the genus
+  fn results() -> (Vec<Term>, Vec<PolynomialResult<1>>) {
+    let normal = Polynomial::<1>::generate_default_lut(3);
+    let classes: Vec<IsoPolynomial<1>> = parser::parse_iso_file::<F2_i<1>, 1>("3 | 2\n1_300 1_030 1_003 | 56\n1_210 1_021 1_102 1_111 | 24\n1_201 1_030 | 8\n", 3, 2, &normal).unwrap();
+    let points = [vec![3, 9], vec![1, 5], vec![]];
+    let results = classes.into_iter().zip(points).map(|(class, points)| {
+      let mut result = PolynomialResult::new(class, points);
+      result.compute_l_polynomial::<F2_i<1>>(1);
+      result
+    }).collect();
+    (normal, results)
+  }
+
+  fn metadata() -> Metadata {
+    Metadata { degree: 3, field_order: 2, max_field_ext: 2, count_ext: 2, runtime: Duration::from_millis(1500), digest: 0xfedcba9876543210 }
+  }
+
+  fn write(format: OutputFormat) -> (Vec<Term>, Vec<PolynomialResult<1>>, String) {
+    let (normal, results) = results();
+    let path = std::env::temp_dir().join(format!("affiene_variety-output-{:?}-{}", format, std::process::id()));
+    let path = path.to_str().unwrap();
+    write_results::<F2_i<1>, 1>(path, format, &metadata(), &results, &normal);
+    let contents = fs::read_to_string(path).unwrap();
+    fs::remove_file(path).unwrap();
+    (normal, results, contents)
+  }
+
+  // The representative is written in the input format, so it parses back to the same polynomial
+  fn parse_representative(representative: &str, size: u32, normal: &[Term]) -> IsoPolynomial<1> {
+    parser::parse_iso_file::<F2_i<1>, 1>(&format!("3 | 2\n{} | {}\n", representative, size), 3, 2, normal).unwrap().remove(0)
+  }
+
+  fn check_json_row(row: &Json, result: &PolynomialResult<1>, normal: &[Term]) {
+    let Json::String(representative) = row.get("representative") else { panic!("representative is not a string") };
+    let Json::Number(size) = row.get("size") else { panic!("size is not a number") };
+    assert_eq!(parse_representative(representative, size.parse().unwrap(), normal), result.poly);
+    let coefficients: Vec<u64> = row.get("coefficients").numbers();
+    assert_eq!(coefficients, (0..normal.len()).map(|i| bits_to_constant::<F2_i<1>>(result.poly.representative.coefficient::<F2_i<1>>(i))).collect::<Vec<_>>());
+    assert_eq!(row.get("points_on_curve").numbers::<usize>(), result.points_on_curve);
+    match (row.get("l_polynomial"), &result.l_polynomial) {
+      (Json::Null, None) => {},
+      (l, Some(expected)) => assert_eq!(l.numbers::<i128>(), expected.coefficients),
+      (l, None) => panic!("unexpected L-polynomial {:?}", l),
+    }
+  }
+
+  fn check_metadata(metadata: &Json) {
+    assert_eq!(metadata.get("degree"), &Json::Number("3".to_owned()));
+    assert_eq!(metadata.get("runtime_seconds"), &Json::Number("1.5".to_owned()));
+    assert_eq!(metadata.get("digest"), &Json::String("fedcba9876543210".to_owned()));
+    let Json::Array(monomials) = metadata.get("monomials") else { panic!("monomials is not an array") };
+    assert_eq!(monomials.len(), 10);
+  }
+
+  #[test]
+  fn text_round_trip() {
+    let (normal, results, contents) = write(OutputFormat::Text);
+    let lines: Vec<&str> = contents.lines().filter(|l| !l.starts_with('#')).collect();
+    assert_eq!(lines.len(), results.len());
+    for (line, result) in lines.iter().zip(&results) {
+      let fields: Vec<&str> = line.split(" | ").collect();
+      assert_eq!(parse_representative(fields[0], fields[1].parse().unwrap(), &normal), result.poly);
+      assert_eq!(fields[2], format!("{:?}", result.points_on_curve));
+      assert_eq!(fields[3], result.l_polynomial.as_ref().map_or("-".to_owned(), |l| l.str()));
+    }
+  }
+
+  // Splits a CSV row into its fields, undoing the quoting of csv_field
+  fn csv_fields(row: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+      match (c, quoted) {
+        ('"', true) if chars.next_if_eq(&'"').is_some() => fields.last_mut().unwrap().push('"'),
+        ('"', _) => quoted = !quoted,
+        (',', false) => fields.push(String::new()),
+        (c, _) => fields.last_mut().unwrap().push(c),
+      }
+    }
+    assert!(!quoted, "unterminated quote in {}", row);
+    fields
+  }
+
+  #[test]
+  fn csv_field_quoting() {
+    assert_eq!(csv_field("1_300 1_030"), "1_300 1_030");
+    assert_eq!(csv_field("1_[10,0,0]"), "\"1_[10,0,0]\"");
+    assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+    assert_eq!(csv_fields(&csv_field("a\"b,c")), vec!["a\"b,c"]);
+  }
+
+  // Exponents of 10 or more are written with commas, the row still has five fields
+  #[test]
+  fn csv_round_trip_of_large_exponents() {
+    let normal = Polynomial::<2>::generate_default_lut(10);
+    let input = "10 | 2\n1_[10,0,0] 1_[0,10,0] 1_[0,0,10] 1_x9y | 1\n";
+    let class = parser::parse_iso_file::<F2_i<1>, 2>(input, 10, 2, &normal).unwrap().remove(0);
+    let results = vec![PolynomialResult::new(class, vec![3])];
+    let metadata = Metadata { degree: 10, field_order: 2, max_field_ext: 1, count_ext: 1, runtime: Duration::ZERO, digest: 0 };
+    let path = std::env::temp_dir().join(format!("affiene_variety-output-large-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    write_results::<F2_i<1>, 2>(path, OutputFormat::Csv, &metadata, &results, &normal);
+    let contents = fs::read_to_string(path).unwrap();
+    fs::remove_file(path).unwrap();
+    let rows: Vec<&str> = contents.lines().filter(|l| !l.starts_with('#')).skip(1).collect();
+    assert_eq!(rows.len(), 1);
+    let fields = csv_fields(rows[0]);
+    assert_eq!(fields.len(), 5);
+    assert!(fields[0].contains("[10,0,0]"));
+    let parsed = parser::parse_iso_file::<F2_i<1>, 2>(&format!("10 | 2\n{} | {}\n", fields[0], fields[2]), 10, 2, &normal).unwrap();
+    assert_eq!(parsed[0], results[0].poly);
+    assert_eq!(fields[3], "3");
+    assert_eq!(fields[4], "");
+  }
+
+  #[test]
+  fn csv_round_trip() {
+    let (normal, results, contents) = write(OutputFormat::Csv);
+    assert!(contents.lines().any(|l| l == "# digest: fedcba9876543210"));
+    let mut lines = contents.lines().filter(|l| !l.starts_with('#'));
+    assert_eq!(lines.next(), Some("representative,coefficients,size,points_on_curve,l_polynomial"));
+    let split = |field: &str| -> Vec<i128> { field.split(';').filter(|v| !v.is_empty()).map(|v| v.parse().unwrap()).collect() };
+    let rows: Vec<&str> = lines.collect();
+    assert_eq!(rows.len(), results.len());
+    for (row, result) in rows.iter().zip(&results) {
+      let fields = csv_fields(row);
+      assert_eq!(fields.len(), 5);
+      assert_eq!(parse_representative(&fields[0], fields[2].parse().unwrap(), &normal), result.poly);
+      assert_eq!(split(&fields[1]), (0..normal.len()).map(|i| bits_to_constant::<F2_i<1>>(result.poly.representative.coefficient::<F2_i<1>>(i)) as i128).collect::<Vec<_>>());
+      assert_eq!(split(&fields[3]), result.points_on_curve.iter().map(|&p| p as i128).collect::<Vec<_>>());
+      assert_eq!(split(&fields[4]), result.l_polynomial.as_ref().map_or(vec![], |l: &LPolynomial| l.coefficients.clone()));
+    }
+  }
+
+  // The last result stopped at k_1, the one before at k_3 after two rungs
+  #[test]
+  fn singular_output() {
+    let (normal, mut results) = results();
+    results[1].points_on_curve.truncate(2);
+    let path = std::env::temp_dir().join(format!("affiene_variety-singular-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    write_singular::<F2_i<1>, 1>(path, &results[1..], &normal);
+    let contents = fs::read_to_string(path).unwrap();
+    fs::remove_file(path).unwrap();
+    let lines: Vec<&str> = contents.lines().filter(|l| !l.starts_with('#')).collect();
+    assert_eq!(lines, vec!["1_021 1_102 1_111 1_210 | 24 | 3 | [1, 5]", "1_030 1_201 | 8 | 1 | []"]);
+  }
+
+  #[test]
+  fn json_round_trip() {
+    let (normal, results, contents) = write(OutputFormat::Json);
+    let json = Json::parse(&contents);
+    check_metadata(json.get("metadata"));
+    let Json::Array(rows) = json.get("results") else { panic!("results is not an array") };
+    assert_eq!(rows.len(), results.len());
+    for (row, result) in rows.iter().zip(&results) {
+      check_json_row(row, result, &normal);
+    }
+  }
+
+  #[test]
+  fn jsonl_round_trip() {
+    let (normal, results, contents) = write(OutputFormat::Jsonl);
+    let lines: Vec<Json> = contents.lines().map(Json::parse).collect();
+    check_metadata(lines[0].get("metadata"));
+    assert_eq!(lines.len(), results.len() + 1);
+    for (row, result) in lines[1..].iter().zip(&results) {
+      check_json_row(row, result, &normal);
+    }
+  }
+}
@@ -0,0 +1,277 @@
+use std::fmt;
+
+use crate::algebraic_types::IsoPolynomial;
+use crate::field_extensions::FieldTraits;
+use crate::polynomials::{constant_to_bits, field_order, Polynomial, Term};
+
+
+//
+//
+// Parser for the isomorphism class files, every error carries the line and column where it was found
+// Blank lines and lines starting with # are skipped, the first other line is the header "degree | field order"
+// Every following line is "term ... term | class size", a term is a constant and a monomial joined by _
+// The monomial is written as three digits (1_023), with variables (1_x2y3z5, a missing exponent is 1)
+// or as a list of exponents (1_[10,0,2]), the last two forms allow exponents of more than one digit
+//
+//
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+  MissingHeader,
+  InvalidHeader(String),
+  DegreeMismatch { file: usize, expected: usize },
+  FieldMismatch { file: usize, expected: usize },
+  MissingSize,
+  InvalidSize(String),
+  EmptyPolynomial,
+  InvalidTerm(String),
+  InvalidConstant { constant: String, field_order: u64 },
+  WrongDegree { monomial: String, degree: usize, expected: usize },
+  UnknownMonomial(String),
+  DuplicateMonomial(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+  // Both start at 1
+  pub line: usize,
+  pub column: usize,
+  pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ParseErrorKind::MissingHeader => write!(f, "missing header, expected \"degree | field order\""),
+      ParseErrorKind::InvalidHeader(header) => write!(f, "invalid header \"{}\", expected \"degree | field order\"", header),
+      ParseErrorKind::DegreeMismatch { file, expected } => write!(f, "degree {} of the file is not equal to --degree {}", file, expected),
+      ParseErrorKind::FieldMismatch { file, expected } => write!(f, "field order {} of the file is not equal to --field {}", file, expected),
+      ParseErrorKind::MissingSize => write!(f, "missing \"| class size\""),
+      ParseErrorKind::InvalidSize(size) => write!(f, "invalid class size \"{}\"", size),
+      ParseErrorKind::EmptyPolynomial => write!(f, "polynomial has no terms"),
+      ParseErrorKind::InvalidTerm(term) => write!(f, "invalid term \"{}\", expected constant_xyz, constant_x2y3z5 or constant_[a,b,c]", term),
+      ParseErrorKind::InvalidConstant { constant, field_order } => write!(f, "constant \"{}\" is not in 1..{}", constant, field_order),
+      ParseErrorKind::WrongDegree { monomial, degree, expected } => write!(f, "monomial \"{}\" has degree {}, expected {}", monomial, degree, expected),
+      ParseErrorKind::UnknownMonomial(monomial) => write!(f, "unknown monomial \"{}\"", monomial),
+      ParseErrorKind::DuplicateMonomial(monomial) => write!(f, "monomial \"{}\" appears more than once", monomial),
+    }
+  }
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "line {}, column {}: {}", self.line, self.column, self.kind)
+  }
+}
+
+
+// Whitespace separated tokens with their byte offset
+fn tokens(s: &str) -> Vec<(usize, &str)> {
+  let mut res = vec![];
+  let mut start = None;
+  for (i, c) in s.char_indices().chain(std::iter::once((s.len(), ' '))) {
+    match (start, c.is_whitespace()) {
+      (None, false) => start = Some(i),
+      (Some(j), true) => {
+        res.push((j, &s[j..i]));
+        start = None;
+      },
+      _ => {},
+    }
+  }
+  res
+}
+
+fn parse_exponents(monomial: &str) -> Option<[usize; 3]> {
+  if let Some(list) = monomial.strip_prefix('[') {
+    let exponents: Vec<usize> = list.strip_suffix(']')?.split(',').map(|e| e.trim().parse().ok()).collect::<Option<_>>()?;
+    return exponents.try_into().ok()
+  }
+
+  if monomial.chars().all(|c| c.is_ascii_digit()) {
+    if monomial.is_empty() || monomial.len() > 3 {
+      return None
+    }
+    let digits = format!("{:0>3}", monomial);
+    let mut res = [0; 3];
+    for (e, c) in res.iter_mut().zip(digits.chars()) {
+      *e = c.to_digit(10)? as usize;
+    }
+    return Some(res)
+  }
+
+  let mut res = [0; 3];
+  let mut seen = [false; 3];
+  let mut chars = monomial.chars().peekable();
+  while let Some(variable) = chars.next() {
+    let i = "xyz".find(variable)?;
+    if seen[i] {
+      return None
+    }
+    seen[i] = true;
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+      digits.push(c);
+    }
+    res[i] = if digits.is_empty() { 1 } else { digits.parse().ok()? };
+  }
+  Some(res)
+}
+
+// Parses the terms of a polynomial, column is the column of the first character of input
+fn parse_polynomial<B: FieldTraits, const W: usize>(input: &str, line: usize, column: usize, lut: &[Term]) -> Result<Polynomial<W>, ParseError> {
+  let degree = lut.first().map_or(0, |t| (t.x_deg + t.y_deg + t.z_deg) as usize);
+  let q = field_order::<B>();
+  let mut coefficients = vec![0; lut.len()];
+  let terms = tokens(input);
+
+  if terms.is_empty() {
+    return Err(ParseError { line, column, kind: ParseErrorKind::EmptyPolynomial })
+  }
+
+  for (offset, term) in terms {
+    let error = |kind| ParseError { line, column: column + offset, kind };
+    let (constant, monomial) = term.split_once('_').ok_or_else(|| error(ParseErrorKind::InvalidTerm(term.to_owned())))?;
+
+    let c = match constant.parse::<u64>() {
+      Ok(c) if c != 0 && c < q => c,
+      _ => return Err(error(ParseErrorKind::InvalidConstant { constant: constant.to_owned(), field_order: q })),
+    };
+
+    let [x, y, z] = parse_exponents(monomial).ok_or_else(|| error(ParseErrorKind::InvalidTerm(term.to_owned())))?;
+    // Exponents can be as large as a usize, so their sum may not fit in one
+    let sum = x.checked_add(y).and_then(|s| s.checked_add(z)).ok_or_else(|| error(ParseErrorKind::InvalidTerm(term.to_owned())))?;
+    if sum != degree {
+      return Err(error(ParseErrorKind::WrongDegree { monomial: monomial.to_owned(), degree: sum, expected: degree }))
+    }
+
+    let index = lut.iter().position(|t| (t.x_deg as usize, t.y_deg as usize, t.z_deg as usize) == (x, y, z))
+      .ok_or_else(|| error(ParseErrorKind::UnknownMonomial(monomial.to_owned())))?;
+    if coefficients[index] != 0 {
+      return Err(error(ParseErrorKind::DuplicateMonomial(monomial.to_owned())))
+    }
+    coefficients[index] = constant_to_bits::<B>(c);
+  }
+
+  Ok(Polynomial::from_coefficient_bits::<B>(&coefficients))
+}
+
+fn parse_header(header: &str) -> Option<(usize, usize)> {
+  let (degree, field_order) = header.split_once('|')?;
+  Some((degree.trim().parse().ok()?, field_order.trim().parse().ok()?))
+}
+
+pub fn parse_iso_file<B: FieldTraits, const W: usize>(input: &str, degree: usize, order: usize, lut: &[Term]) -> Result<Vec<IsoPolynomial<W>>, ParseError> {
+  let mut lines = input.lines().enumerate()
+    .map(|(i, l)| (i + 1, l))
+    .filter(|(_, l)| !l.trim().is_empty() && !l.trim_start().starts_with('#'));
+
+  let (line, header) = lines.next().ok_or(ParseError { line: input.lines().count().max(1), column: 1, kind: ParseErrorKind::MissingHeader })?;
+  let column = header.len() - header.trim_start().len() + 1;
+  let (file_degree, file_order) = parse_header(header)
+    .ok_or_else(|| ParseError { line, column, kind: ParseErrorKind::InvalidHeader(header.trim().to_owned()) })?;
+  if file_degree != degree {
+    return Err(ParseError { line, column, kind: ParseErrorKind::DegreeMismatch { file: file_degree, expected: degree } })
+  }
+  if file_order != order {
+    return Err(ParseError { line, column, kind: ParseErrorKind::FieldMismatch { file: file_order, expected: order } })
+  }
+
+  let mut iso_polys = vec![];
+  for (line, l) in lines {
+    let (polynomial, size) = l.split_once('|').ok_or(ParseError { line, column: l.len() + 1, kind: ParseErrorKind::MissingSize })?;
+    let size_column = polynomial.len() + 2;
+    let size = match tokens(size).as_slice() {
+      [(_, s)] => s.parse::<u32>().map_err(|_| ParseError { line, column: size_column, kind: ParseErrorKind::InvalidSize(s.to_string()) })?,
+      _ => return Err(ParseError { line, column: size_column, kind: ParseErrorKind::InvalidSize(size.trim().to_owned()) }),
+    };
+
+    iso_polys.push(IsoPolynomial {
+      representative: parse_polynomial::<B, W>(polynomial, line, 1, lut)?,
+      size,
+    });
+  }
+  Ok(iso_polys)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::field_extensions::F3_i;
+
+  fn parse(input: &str) -> Result<Vec<IsoPolynomial<1>>, ParseError> {
+    parse_iso_file::<F3_i<1>, 1>(input, 3, 3, &Polynomial::<1>::generate_default_lut(3))
+  }
+
+  fn error(input: &str) -> (usize, usize, ParseErrorKind) {
+    let error = parse(input).unwrap_err();
+    (error.line, error.column, error.kind)
+  }
+
+  // Polynomial of degree 3 over F3 from (a, b, c, constant) for constant x^a y^b z^c
+  fn polynomial(terms: &[(u8, u8, u8, u64)]) -> Polynomial<1> {
+    let lut = Polynomial::<1>::generate_default_lut(3);
+    let bits: Vec<u64> = lut.iter().map(|t| {
+      terms.iter().find(|&&(a, b, c, _)| (a, b, c) == (t.x_deg, t.y_deg, t.z_deg)).map_or(0, |&(_, _, _, constant)| constant_to_bits::<F3_i<1>>(constant))
+    }).collect();
+    Polynomial::from_coefficient_bits::<F3_i<1>>(&bits)
+  }
+
+  #[test]
+  fn monomial_syntaxes() {
+    let expected = polynomial(&[(3, 0, 0, 1), (1, 1, 1, 2), (0, 0, 3, 1)]);
+    for line in ["1_300 2_111 1_003", "1_x3 2_xyz 1_z3", "1_[3,0,0] 2_[1,1,1] 1_[0,0,3]", "1_x3y0 2_111 1_3"] {
+      let classes = parse(&format!("# comment\n\n3 | 3\n{} | 24\n", line)).unwrap();
+      assert_eq!(classes, vec![IsoPolynomial { representative: expected, size: 24 }], "{}", line);
+    }
+  }
+
+  #[test]
+  fn header_errors() {
+    assert_eq!(error("# only a comment\n"), (1, 1, ParseErrorKind::MissingHeader));
+    assert_eq!(error("\n  3 3\n"), (2, 3, ParseErrorKind::InvalidHeader("3 3".to_owned())));
+    assert_eq!(error("4 | 3\n"), (1, 1, ParseErrorKind::DegreeMismatch { file: 4, expected: 3 }));
+    assert_eq!(error("3 | 2\n"), (1, 1, ParseErrorKind::FieldMismatch { file: 2, expected: 3 }));
+  }
+
+  #[test]
+  fn size_errors() {
+    assert_eq!(error("3 | 3\n1_300 1_030"), (2, 12, ParseErrorKind::MissingSize));
+    assert_eq!(error("3 | 3\n1_300 | 2x"), (2, 8, ParseErrorKind::InvalidSize("2x".to_owned())));
+    assert_eq!(error("3 | 3\n1_300 | 2 4"), (2, 8, ParseErrorKind::InvalidSize("2 4".to_owned())));
+  }
+
+  #[test]
+  fn term_errors() {
+    assert_eq!(error("3 | 3\n  | 1"), (2, 1, ParseErrorKind::EmptyPolynomial));
+    assert_eq!(error("3 | 3\n1_300 2xyz | 1"), (2, 7, ParseErrorKind::InvalidTerm("2xyz".to_owned())));
+    assert_eq!(error("3 | 3\n1_300 1_xx2 | 1"), (2, 7, ParseErrorKind::InvalidTerm("1_xx2".to_owned())));
+    assert_eq!(error("3 | 3\n1_300 1_[1,2] | 1"), (2, 7, ParseErrorKind::InvalidTerm("1_[1,2]".to_owned())));
+    assert_eq!(error("3 | 3\n1_300 1_1111 | 1"), (2, 7, ParseErrorKind::InvalidTerm("1_1111".to_owned())));
+    assert_eq!(error("3 | 3\n1_300  3_030 | 1"), (2, 8, ParseErrorKind::InvalidConstant { constant: "3".to_owned(), field_order: 3 }));
+    assert_eq!(error("3 | 3\n0_300 | 1"), (2, 1, ParseErrorKind::InvalidConstant { constant: "0".to_owned(), field_order: 3 }));
+    assert_eq!(error("3 | 3\n1_300 1_x2 | 1"), (2, 7, ParseErrorKind::WrongDegree { monomial: "x2".to_owned(), degree: 2, expected: 3 }));
+    assert_eq!(error("3 | 3\n1_x3 2_[3,0,0] | 1"), (2, 6, ParseErrorKind::DuplicateMonomial("[3,0,0]".to_owned())));
+  }
+
+  // Exponents whose sum does not fit in a usize are an invalid term, not an overflow
+  #[test]
+  fn huge_exponents() {
+    let term = format!("1_[{},1,0]", usize::MAX);
+    assert_eq!(error(&format!("3 | 3\n1_300 {} | 1", term)), (2, 7, ParseErrorKind::InvalidTerm(term.clone())));
+    let term = format!("1_x{}y", usize::MAX);
+    assert_eq!(error(&format!("3 | 3\n{} | 1", term)), (2, 1, ParseErrorKind::InvalidTerm(term.clone())));
+  }
+
+  // Every monomial of the degree is in the default table, so only a table missing some gives this error
+  #[test]
+  fn unknown_monomial() {
+    let lut = Polynomial::<1>::generate_default_lut(3);
+    let missing = lut[0];
+    let monomial = format!("[{},{},{}]", missing.x_deg, missing.y_deg, missing.z_deg);
+    let error = parse_iso_file::<F3_i<1>, 1>(&format!("3 | 3\n1_{} | 1", monomial), 3, 3, &lut[1..]).unwrap_err();
+    assert_eq!((error.line, error.column, error.kind), (2, 1, ParseErrorKind::UnknownMonomial(monomial)));
+  }
+}
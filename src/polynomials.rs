@@ -1,10 +1,15 @@
+use std::ops::Range;
 
-use crate::{DPLUS2_CHOOSE_2, algebraic_types::Lookup, DEGREE, field_extensions::{F2_i, FieldTraits, F3_i}, FIELD_ORDER};
 
+use crate::{algebraic_types::{BaseEmbedding, Lookup}, field_extensions::FieldTraits, groebner::{self, SmoothnessCertificate}, lookup_table::{PackedTable, Row}, matrix::Matrix3};
 
+
+// The coefficients of all monomials packed next to each other in W words of 64 bits
+// W is picked by the degree and field (see Config::words), so small degrees stay a single u64
+// Coefficients take a power of two bits, so a coefficient never straddles two words
 #[derive(Debug, Copy, Clone, PartialEq)]
-pub struct Polynomial {
-  pub bits: u64
+pub struct Polynomial<const W: usize> {
+  pub bits: [u64; W]
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -13,118 +18,158 @@ pub enum Singularity{
   NonSingular,
 }
 
-impl Polynomial {
+impl<const W: usize> Polynomial<W> {
   #[allow(dead_code)]
-  pub fn new(bits: u64) -> Polynomial {
-    Polynomial { bits: bits }
-  }
-
-  pub fn str(&self, lut: &Vec<Term>) -> String {
-    let mut poly_str = String::new();
-    let mut empty = true;
-    for i in 0..DPLUS2_CHOOSE_2 {
-      if FIELD_ORDER == 2 {
-        if (self.bits >> i) & 1 == 1 {
-          if lut[i].constant != 0 { 
-            if empty {
-              poly_str = format!("1_{}", lut[i].str());
-              empty = false;
-            } else {
-              poly_str = format!("{} 1_{}", poly_str, lut[i].str());
-            }
-          }
-        }
-      } else if FIELD_ORDER == 3 {        
-        if (self.bits >> 2*i) & 0b01 == 1 {
-          if lut[i].constant != 0 { 
-            if empty {
-              poly_str = format!("1_{}", lut[i].str());
-              empty = false;
-            } else {
-              poly_str = format!("{} 1_{}", poly_str, lut[i].str());
-            }
-          }
-        }
-        if (self.bits >> 2*i) & 0b10 == 2 {
-          if lut[i].constant != 0 { 
-            if empty {
-              poly_str = format!("2_{}", lut[i].str());
-              empty = false;
-            } else {
-              poly_str = format!("{} 2_{}", poly_str, lut[i].str());
-            }
-          }
-        }
-      }
+  pub fn new(bits: [u64; W]) -> Polynomial<W> {
+    Polynomial { bits }
+  }
+
+  // The bits from offset onwards, up to the end of the word they are in
+  fn bits_at(&self, offset: usize) -> u64 {
+    self.bits[offset / 64] >> (offset % 64)
+  }
+
+  // Coefficient of the i-th monomial of the lookup table, as the bits of an element of B
+  pub fn coefficient<B: FieldTraits>(&self, i: usize) -> u64 {
+    self.packed_coefficient(i, B::PACKED_BITS)
+  }
+
+  // The same, for when only the amount of bits per coefficient is known
+  pub fn packed_coefficient(&self, i: usize, coeff_bits: usize) -> u64 {
+    self.bits_at(coeff_bits * i) & ((1 << coeff_bits) - 1)
+  }
+
+  fn add_coefficient<B: FieldTraits>(&mut self, i: usize, bits: u64) {
+    let offset = B::PACKED_BITS * i;
+    self.bits[offset / 64] += bits << (offset % 64);
+  }
+
+  // Packs the coefficients of all monomials of the lookup table, given as bits of elements of B
+  pub fn from_coefficient_bits<B: FieldTraits>(coefficients: &[u64]) -> Polynomial<W> {
+    let mut poly = Polynomial { bits: [0; W] };
+    for (i, &bits) in coefficients.iter().enumerate() {
+      poly.add_coefficient::<B>(i, bits);
     }
-    poly_str
+    poly
   }
 
-  #[allow(dead_code)]
-  pub fn print(&self, lut: &Vec<Term>) {
-    println!("{}", self.str(lut));
+  // The coefficients of the first n monomials of the lookup table as elements of B
+  pub fn coefficients<B: FieldTraits>(&self, n: usize) -> Vec<B> {
+    (0..n).map(|i| B::from_bits(self.coefficient::<B>(i))).collect()
+  }
+
+  // The polynomial f(M (x, y, z)), so x gets replaced by m00 x + m01 y + m02 z and so on
+  pub fn substitute<B: FieldTraits>(&self, m: &Matrix3<B>, lut: &[Term]) -> Polynomial<W> {
+    let bits: Vec<u64> = substitute_coefficients(&self.coefficients::<B>(lut.len()), m, lut).into_iter().map(|c| c.to_bits()).collect();
+    Polynomial::from_coefficient_bits::<B>(&bits)
   }
 
+  // Smoothness over the algebraic closure through Groebner bases, independent of any lookup table
+  pub fn is_smooth_exact<B: FieldTraits>(&self, lut: &[Term]) -> SmoothnessCertificate<B> {
+    groebner::is_smooth_exact(&self.coefficients::<B>(lut.len()), lut)
+  }
+
+  // Multiplies every coefficient by the same constant, given as the bits of an element of B
   #[allow(dead_code)]
-  pub fn evaluate_f2<const N: u8>(self, index: usize, lut: &Vec<Vec<F2_i<N>>>) -> F2_i<N> {
-    let mut res = F2_i::ZERO;
-    let index_lut = &lut[index];
-    for i in 0..DPLUS2_CHOOSE_2 {
-      if (self.bits >> i) & 1 == 1 {
-        res += index_lut[i];
-      }
-    }
-    res
+  pub fn multiply_by_constant<B: FieldTraits>(self, constant: u64) -> Polynomial<W> {
+    Polynomial { bits: self.bits.map(|word| multiply_bits_by_constant::<B>(word, constant)) }
   }
 
-  pub fn evaluate_f3<const N: u8>(self, index: usize, lut: &Vec<Vec<F3_i<N>>>) -> F3_i<N> {
-    let mut res = F3_i::ZERO;
-    let index_lut = &lut[index];
-    for i in 0..DPLUS2_CHOOSE_2 {
-      if (self.bits >> (2*i)) & 1 == 1 {
-        res += index_lut[i];
-      }
-      if self.bits >> (2*i) & 2 == 2 {
-        res += index_lut[i];
-        res += index_lut[i];
+  pub fn str<B: FieldTraits>(&self, lut: &[Term]) -> String {
+    let mut terms = vec![];
+    for (i, term) in lut.iter().enumerate() {
+      let coefficient = self.coefficient::<B>(i);
+      if coefficient != 0 && term.constant != 0 {
+        terms.push(format!("{}_{}", bits_to_constant::<B>(coefficient), term.str()));
       }
     }
-    res
+    terms.join(" ")
   }
 
+  #[allow(dead_code)]
+  pub fn print<F: FieldTraits>(&self, lut: &[Term]) {
+    println!("{}", self.str::<F>(lut));
+  }
 
-  // FIELD_ORDER_PROBLEM
-  pub fn has_singularity_point<const N: u8>(self, index: usize,lookup: &Lookup<N>, count: &mut usize) -> Singularity {
-    if self.evaluate_f2(index, &lookup.normal) == F2_i::ZERO {
-      *count += 1;
-      if self.evaluate_f2(index, &lookup.part_x) == F2_i::ZERO {
-        if self.evaluate_f2(index, &lookup.part_y) == F2_i::ZERO {
-          if self.evaluate_f2(index, &lookup.part_z) == F2_i::ZERO {
-            return Singularity::Singular
+  // Evaluates the polynomial in a point of a lookup table, coefficient i goes with the value in column columns.start + i
+  // Over a prime field every coefficient is a number, so adding the monomial that many times suffices
+  // Otherwise every coefficient is split into its prime field coefficients, which get multiplied by the basis once at the end
+  pub fn evaluate<F: FieldTraits>(self, row: &Row<F>, columns: Range<usize>, embedding: &BaseEmbedding<F>) -> F {
+    #[cfg(test)]
+    tests::EVALUATIONS.with(|e| e.set(e.get() + 1));
+    let mask = (1 << embedding.coeff_bits) - 1;
+    let first = columns.start;
+    if embedding.basis.len() == 1 {
+      let mut res = F::ZERO;
+      for column in columns {
+        let coefficient = self.bits_at(embedding.coeff_bits * (column - first)) & mask;
+        if coefficient != 0 {
+          let value = row.get(column);
+          for _ in 0..coefficient {
+            res += value;
           }
         }
       }
+      return res
     }
-    Singularity::NonSingular
+
+    let digit_mask = (1 << F::COEFF_BITS) - 1;
+    let mut parts = [F::ZERO; 4];
+    for column in columns {
+      let coefficient = self.bits_at(embedding.coeff_bits * (column - first)) & mask;
+      if coefficient == 0 {
+        continue;
+      }
+      let value = row.get(column);
+      for (j, part) in parts.iter_mut().enumerate().take(embedding.basis.len()) {
+        for _ in 0..(coefficient >> (F::COEFF_BITS * j)) & digit_mask {
+          *part += value;
+        }
+      }
+    }
+    embedding.basis.iter().zip(parts).fold(F::ZERO, |acc, (&b, part)| acc + b * part)
   }
 
-  pub fn has_singularity<const N: u8>(self, lookup: &Lookup<N>) -> Option<usize> {
-    let mut points_on_curve = 0;
+  // A point is singular when F, F_x, F_y and F_z all vanish in it, the partials are only evaluated on the curve
+  // With euler one of them is left out: Euler's formula d F = x F_x + y F_y + z F_z gives x F_x + y F_y + z F_z = 0 on the curve,
+  // so once two partials vanish so does the one of a nonzero coordinate of the point, whether p divides d or not
+  pub fn has_singularity_point<F: FieldTraits>(self, lookup: &Lookup<F>, table: &PackedTable<F>, row: usize, point: (F, F, F), count: &mut usize, euler: bool) -> Singularity {
+    let (m, embedding, row) = (lookup.monomials(), &lookup.embedding, table.row(row));
+    if self.evaluate(&row, 0..m, embedding) != F::ZERO {
+      return Singularity::NonSingular
+    }
+    *count += 1;
+    let skipped = if euler { [point.0, point.1, point.2].iter().rposition(|&c| c != F::ZERO) } else { None };
+    if (0..3).filter(|&i| Some(i) != skipped).all(|i| self.evaluate(&row, (i + 1) * m..(i + 2) * m, embedding) == F::ZERO) {
+      return Singularity::Singular
+    }
+    Singularity::NonSingular
+  }
 
-    // FIELD_ORDER_PROBLEM
-    for (index, _) in F3_i::<N>::iterate_over_points().enumerate() {
-      if self.has_singularity_point(index,lookup, &mut points_on_curve) == Singularity::Singular {
-        return None
+  // The lookup table already fixes the field, so we walk over exactly its points
+  // Those are one point of every closed point of exact degree k, so this counts closed points rather than points
+  // Every block of the table is used for all polynomials before the next one, which matters when blocks are generated
+  pub fn has_singularity_batch<F: FieldTraits>(polys: &[Polynomial<W>], lookup: &Lookup<F>, euler: bool) -> Vec<Option<usize>> {
+    let mut points_on_curve = vec![Some(0); polys.len()];
+    lookup.for_each_block(|_, points, table| {
+      for (poly, result) in polys.iter().zip(points_on_curve.iter_mut()) {
+        let Some(mut count) = *result else { continue };
+        let singular = points.iter().enumerate().any(|(row, &point)| {
+          poly.has_singularity_point(lookup, table, row, point, &mut count, euler) == Singularity::Singular
+        });
+        *result = (!singular).then_some(count);
       }
-    }
-    Some(points_on_curve)
+      points_on_curve.iter().any(|p| p.is_some())
+    });
+    points_on_curve
   }
 
-  pub fn generate_default_lut() -> Vec<Term> {
-    (0..=DEGREE as u8)
+  pub fn generate_default_lut(degree: usize) -> Vec<Term> {
+    let degree = degree as u8;
+    (0..=degree)
         .flat_map(move |a| {
-            (0..=(DEGREE as u8) - a).map(move |b| {
-                let c = DEGREE as u8 - b - a;
+            (0..=degree - a).map(move |b| {
+                let c = degree - b - a;
                 Term {
                     x_deg: a,
                     y_deg: b,
@@ -136,13 +181,13 @@ impl Polynomial {
         .collect()
   }
 
-  pub fn generate_derative_luts(default_lut: &Vec<Term>) -> (Vec<Term>, Vec<Term>, Vec<Term>) {
+  pub fn generate_derative_luts(default_lut: &[Term], characteristic: usize) -> (Vec<Term>, Vec<Term>, Vec<Term>) {
     let mut lut_x: Vec<Term> = vec![];
     let mut lut_y: Vec<Term> = vec![];
     let mut lut_z: Vec<Term> = vec![];
 
     for term in default_lut {
-      let (x,y,z) = term.generate_derivatives();
+      let (x,y,z) = term.generate_derivatives(characteristic as u8);
       lut_x.push(x);
       lut_y.push(y);
       lut_z.push(z);
@@ -150,50 +195,6 @@ impl Polynomial {
 
     (lut_x, lut_y, lut_z)
   }
-
-  pub fn from_string(input: &str, lut: &Vec<Term>) -> Polynomial {
-
-    let mut poly: u64 = 0;
-
-    for str_term in input.split_whitespace() {
-      let mut iter = str_term.split("_");
-      let constant = iter.next().unwrap().parse::<u64>().unwrap();
-      let powers = iter.next().unwrap().parse::<u64>().unwrap();
-      let z = powers % 10;
-      let y = (powers / 10) % 10;
-      let x = (powers / 100) % 10;
-      
-      let t = Term {
-        x_deg: x as u8,
-        y_deg: y as u8,
-        z_deg: z as u8,
-        constant: 1,
-        };
-
-      for (index, term) in lut.iter().enumerate() {
-        if t.is_similar(*term) {
-          match FIELD_ORDER {
-            2 =>  {
-                    match constant {
-                      1 => poly += 1 << index,
-                      _ => panic!("Invalid constant in imported file")
-                    };
-                  },
-            3 =>  {
-                    match constant {
-                      1 => poly += 0b01 << 2*index,
-                      2 => poly += 0b10 << 2*index,
-                      _ => panic!("Invalid constant in imported file")
-                    };
-                  },
-            _ => {},
-          }
-        }
-      }
-    }
-
-    Polynomial { bits: poly }
-  }
 }
 
 
@@ -205,24 +206,91 @@ pub struct Term {
   pub constant: u8,
 }
 
-pub fn multiply_bits_by_constant(bits: u64, constant: u64) -> u64 {
-  match constant % FIELD_ORDER as u64 {
-    0 => 0,
-    1 => bits,
+pub fn field_order<B: FieldTraits>() -> u64 {
+  (B::CHARACTERISTIC as u64).pow(B::DEGREE as u32)
+}
+
+// Constants in files are numbers below q, an element of F_p^r with coefficients a_i is written as the number sum a_i p^i
+pub fn constant_to_bits<B: FieldTraits>(constant: u64) -> u64 {
+  let p = B::CHARACTERISTIC as u64;
+  (0..B::DEGREE).fold(0, |acc, i| acc | (((constant / p.pow(i as u32)) % p) << (B::COEFF_BITS * i)))
+}
+
+pub fn bits_to_constant<B: FieldTraits>(bits: u64) -> u64 {
+  let element = B::from_bits(bits);
+  let p = B::CHARACTERISTIC as u64;
+  (0..B::DEGREE).fold(0, |acc, i| acc + element.digit(i) * p.pow(i as u32))
+}
+
+// Multiplies every coefficient in a word of a packed polynomial over B by the same constant, given as the bits of an element of B
+pub fn multiply_bits_by_constant<B: FieldTraits>(bits: u64, constant: u64) -> u64 {
+  match (B::CHARACTERISTIC, B::DEGREE, constant) {
+    (_, _, 0) => 0,
+    (_, _, 1) => bits,
+    (2, 1, _) => bits,
+    (3, 1, _) => {
+      const M1: u64 = 0x5555555555555555; 
+      const M2: u64 = 0xAAAAAAAAAAAAAAAA;
+      let a1 = (bits & M2) >> 1;
+      let a2 = (bits & M1) << 1;
+      a1 | a2
+    },
     _ => {
-      match FIELD_ORDER {
-        2 => bits,
-        3 =>  {
-          const M1: u64 = 0x5555555555555555; 
-          const M2: u64 = 0xAAAAAAAAAAAAAAAA;
-          let a1 = (bits & M2) >> 1;
-          let a2 = (bits & M1) << 1;
-          a1 | a2
-        },
-        _ => panic!("Field size not supported"),
+      let constant = B::from_bits(constant);
+      let mask = (1 << B::PACKED_BITS) - 1;
+      (0..64 / B::PACKED_BITS).fold(0, |acc, i| {
+        let coefficient = B::from_bits((bits >> (B::PACKED_BITS * i)) & mask);
+        acc | ((coefficient * constant).to_bits() << (B::PACKED_BITS * i))
+      })
+    },
+  }
+}
+
+// Index of the monomial x^a y^b z^(e-a-b) in the ordering of Polynomial::generate_default_lut(e)
+pub fn monomial_index(e: usize, a: usize, b: usize) -> usize {
+  a * (e + 1) - (a * a - a) / 2 + b
+}
+
+// Product of two homogeneous polynomials of degree e1 and e2, given by their coefficients in lookup table order
+pub fn multiply_dense<F: FieldTraits>(lhs: &[F], e1: usize, rhs: &[F], e2: usize) -> Vec<F> {
+  let e = e1 + e2;
+  let mut res = vec![F::ZERO; (e + 1) * (e + 2) / 2];
+  for a1 in 0..=e1 {
+    for b1 in 0..=e1 - a1 {
+      let l = lhs[monomial_index(e1, a1, b1)];
+      if l == F::ZERO {
+        continue;
+      }
+      for a2 in 0..=e2 {
+        for b2 in 0..=e2 - a2 {
+          res[monomial_index(e, a1 + a2, b1 + b2)] += l * rhs[monomial_index(e2, a2, b2)];
+        }
       }
     }
   }
+  res
+}
+
+// The images of every monomial of the lookup table under the substitution by M, as coefficients in the same lookup table
+// Powers of the linear forms are multiplied out in the field itself, so binomial coefficients automatically vanish mod p
+pub fn substitute_monomials<F: FieldTraits>(m: &Matrix3<F>, lut: &[Term]) -> Vec<Vec<F>> {
+  let degree = lut[0].x_deg as usize + lut[0].y_deg as usize + lut[0].z_deg as usize;
+
+  // powers[j][e] is the e-th power of the linear form that replaces the j-th variable
+  let powers: Vec<Vec<Vec<F>>> = m.rows.iter().map(|row| {
+    let mut powers = vec![vec![F::ONE]];
+    for e in 0..degree {
+      let next = multiply_dense(&powers[e], e, &[row[2], row[1], row[0]], 1);
+      powers.push(next);
+    }
+    powers
+  }).collect();
+
+  lut.iter().map(|t| {
+    let (a, b, c) = (t.x_deg as usize, t.y_deg as usize, t.z_deg as usize);
+    let xy = multiply_dense(&powers[0][a], a, &powers[1][b], b);
+    multiply_dense(&xy, a + b, &powers[2][c], c)
+  }).collect()
 }
 
 
@@ -231,91 +299,135 @@ impl Term {
     Term { x_deg: 0, y_deg: 0, z_deg: 0, constant: 0 }
   }
 
-  #[allow(dead_code)]
-  pub fn evaluate_f2<const N: u8>(self, x: F2_i<N>, y: F2_i<N>, z: F2_i<N>) -> F2_i<N> {
+  // The constant is an element of the prime field, for derivatives it is the exponent that came down mod p
+  pub fn evaluate<F: FieldTraits>(self, x: F, y: F, z: F) -> F {
     if self.constant == 0 {
-      F2_i::ZERO
+      F::ZERO
     } else {
-      x.mul_ntimes(self.x_deg) * y.mul_ntimes(self.y_deg) * z.mul_ntimes(self.z_deg)
-    }
-  }
-
-  pub fn evaluate_f3<const N: u8>(self, x: F3_i<N>, y: F3_i<N>, z: F3_i<N>) -> F3_i<N> {
-    if self.constant == 0 {
-      F3_i::ZERO
-    } else {
-      x.mul_ntimes(self.x_deg) * y.mul_ntimes(self.y_deg) * z.mul_ntimes(self.z_deg) 
-      // * F3_i{ element: self.constant as u16 }
+      (x.mul_ntimes(self.x_deg) * y.mul_ntimes(self.y_deg) * z.mul_ntimes(self.z_deg)).scale(self.constant as u64)
     }
   }
 
+  // Exponents of more than one digit need the list form
   pub fn str(self) -> String {
+    if self.x_deg.max(self.y_deg).max(self.z_deg) >= 10 {
+      return format!("[{},{},{}]", self.x_deg, self.y_deg, self.z_deg)
+    }
     format!("{}{}{}", self.x_deg, self.y_deg, self.z_deg)
   }
   
-  pub fn generate_derivatives(self) -> (Term, Term, Term) {
+  pub fn generate_derivatives(self, characteristic: u8) -> (Term, Term, Term) {
     let mut term_x = self;
     let mut term_y = self;
     let mut term_z = self;
 
-    if term_x.x_deg % FIELD_ORDER as u8 == 0 {
+    if term_x.x_deg.is_multiple_of(characteristic) {
       term_x = Term::zero();
     } else {
-      term_x.constant = (term_x.constant * term_x.x_deg) % FIELD_ORDER as u8;
+      term_x.constant = ((term_x.constant as usize * term_x.x_deg as usize) % characteristic as usize) as u8;
       term_x.x_deg -= 1;
     }
 
-    if term_y.y_deg % FIELD_ORDER as u8 == 0 {
+    if term_y.y_deg.is_multiple_of(characteristic) {
       term_y = Term::zero();
     } else {
-      term_y.constant = (term_y.constant * term_y.y_deg) % FIELD_ORDER as u8;
+      term_y.constant = ((term_y.constant as usize * term_y.y_deg as usize) % characteristic as usize) as u8;
       term_y.y_deg -= 1;
     }
     
-    if term_z.z_deg % FIELD_ORDER as u8 == 0 {
+    if term_z.z_deg.is_multiple_of(characteristic) {
       term_z = Term::zero();
     } else {
-      term_z.constant = (term_z.constant * term_z.z_deg) % FIELD_ORDER as u8;
+      term_z.constant = ((term_z.constant as usize * term_z.z_deg as usize) % characteristic as usize) as u8;
       term_z.z_deg -= 1;
     }
     (term_x, term_y, term_z)
   }
+}
 
-  
-  pub fn generate_precalculated_points<const N: u8>(self) -> Vec<F2_i<N>> {
-    let mut results = Vec::new();
-    for (x,y,z) in F2_i::iterate_over_points() {
-      let mut result = self.evaluate_f2(x, y, z);
-      // result.element = multiply_bits_by_constant(result.element as u64, self.constant as u64) as u16;
-      results.push(result);
+// The coefficients of f(M (x, y, z)) from those of f, for when they live in an extension rather than in B
+pub fn substitute_coefficients<F: FieldTraits>(coefficients: &[F], m: &Matrix3<F>, lut: &[Term]) -> Vec<F> {
+  let mut res = vec![F::ZERO; lut.len()];
+  for (&c, image) in coefficients.iter().zip(substitute_monomials(m, lut)) {
+    if c != F::ZERO {
+      for (r, v) in res.iter_mut().zip(image) {
+        *r += c * v;
+      }
     }
-    results
   }
+  res
+}
 
-  // FIELD_ORDER_PROBLEM
-  pub fn generate_points_for_multiple<const N: u8>(terms: &Vec<Term>) -> Vec<Vec<F2_i<N>>> {
-    let mut resultant_terms = Vec::new();
-    for t in terms {
-      resultant_terms.push(t.generate_precalculated_points());
-    }
-    transpose(resultant_terms)
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+  use std::cell::Cell;
+  use crate::field_extensions::{F2_i, F3_i, F5_i, F7_i};
+
+  thread_local! {
+    // Calls of Polynomial::evaluate on this thread, to compare the work of the singularity tests
+    pub(crate) static EVALUATIONS: Cell<usize> = const { Cell::new(0) };
   }
 
-fn is_similar(&self, term: Term) -> bool {
-  self.x_deg == term.x_deg && self.y_deg == term.y_deg && self.z_deg == term.z_deg
-}
-}
+  // Polynomial of the given degree from (a, b, c, constant) for constant x^a y^b z^c, constants as in the input files
+  pub(crate) fn polynomial<B: FieldTraits>(degree: usize, terms: &[(u8, u8, u8, u64)]) -> (Polynomial<4>, Vec<Term>) {
+    let lut = Polynomial::<4>::generate_default_lut(degree);
+    let bits: Vec<u64> = lut.iter().map(|t| {
+      terms.iter().find(|&&(a, b, c, _)| (a, b, c) == (t.x_deg, t.y_deg, t.z_deg)).map_or(0, |&(_, _, _, constant)| constant_to_bits::<B>(constant))
+    }).collect();
+    (Polynomial::from_coefficient_bits::<B>(&bits), lut)
+  }
 
-fn transpose<T>(v: Vec<Vec<T>>) -> Vec<Vec<T>> {
-  assert!(!v.is_empty());
-  let len = v[0].len();
-  let mut iters: Vec<_> = v.into_iter().map(|n| n.into_iter()).collect();
-  (0..len)
-    .map(|_| {
-        iters
-            .iter_mut()
-            .map(|n| n.next().unwrap())
-            .collect::<Vec<T>>()
-    })
-    .collect()
-}
\ No newline at end of file
+  fn matrix<B: FieldTraits>(rows: [[u64; 3]; 3]) -> Matrix3<B> {
+    Matrix3::new(rows.map(|row| row.map(|c| B::from_bits(constant_to_bits::<B>(c)))))
+  }
+
+  fn check_round_trip<B: FieldTraits>(degree: usize, terms: &[(u8, u8, u8, u64)], m: Matrix3<B>) {
+    let (f, lut) = polynomial::<B>(degree, terms);
+    let g = f.substitute(&m, &lut);
+    assert_ne!(g, f);
+    assert_eq!(g.substitute(&m.inverse().unwrap(), &lut), f);
+  }
+
+  #[test]
+  fn substitute_and_back() {
+    check_round_trip::<F3_i<1>>(4, &[(4, 0, 0, 1), (1, 2, 1, 2), (0, 0, 4, 1), (2, 1, 1, 1)], matrix([[1, 2, 0], [0, 1, 1], [2, 0, 1]]));
+    check_round_trip::<F2_i<2>>(3, &[(3, 0, 0, 1), (0, 3, 0, 2), (1, 1, 1, 3), (0, 0, 3, 1)], matrix([[2, 1, 0], [3, 0, 1], [1, 0, 1]]));
+    check_round_trip::<F7_i<1>>(3, &[(3, 0, 0, 1), (0, 3, 0, 1), (0, 0, 3, 1), (1, 1, 1, 4)], matrix([[3, 5, 6], [1, 0, 2], [4, 4, 2]]));
+  }
+
+  // x^p becomes (x + c y)^p = x^p + c^p y^p, every other binomial coefficient vanishes mod p
+  fn check_frobenius<B: FieldTraits>(p: usize, c: u64, c_p: u64) {
+    let (x_p, lut) = polynomial::<B>(p, &[(p as u8, 0, 0, 1)]);
+    let (expected, _) = polynomial::<B>(p, &[(p as u8, 0, 0, 1), (0, p as u8, 0, c_p)]);
+    assert_eq!(x_p.substitute(&matrix::<B>([[1, c, 0], [0, 1, 0], [0, 0, 1]]), &lut), expected);
+  }
+
+  #[test]
+  fn frobenius_in_characteristic_p() {
+    check_frobenius::<F2_i<1>>(2, 1, 1);
+    check_frobenius::<F3_i<1>>(3, 1, 1);
+    check_frobenius::<F5_i<1>>(5, 2, 2);
+    check_frobenius::<F7_i<1>>(7, 3, 3);
+    // In F4 = F2(w) with w^2 = w + 1, w is constant 2 and w^2 constant 3
+    check_frobenius::<F2_i<2>>(2, 2, 3);
+  }
+
+  // The derivative keeps the exponent as a constant mod p
+  #[test]
+  fn derivative_constants() {
+    let term = |x_deg, y_deg, z_deg, constant| Term { x_deg, y_deg, z_deg, constant };
+    assert_eq!(term(2, 0, 1, 1).generate_derivatives(3), (term(1, 0, 1, 2), Term::zero(), term(2, 0, 0, 1)));
+    assert_eq!(term(3, 0, 0, 1).generate_derivatives(3), (Term::zero(), Term::zero(), Term::zero()));
+    assert_eq!(term(1, 4, 0, 1).generate_derivatives(5), (term(0, 4, 0, 1), term(1, 3, 0, 4), Term::zero()));
+    assert_eq!(term(0, 3, 2, 1).generate_derivatives(2), (Term::zero(), term(0, 2, 2, 1), Term::zero()));
+  }
+
+  #[test]
+  fn binomial_coefficients_below_p() {
+    let (x_2, lut) = polynomial::<F3_i<1>>(2, &[(2, 0, 0, 1)]);
+    let (expected, _) = polynomial::<F3_i<1>>(2, &[(2, 0, 0, 1), (1, 1, 0, 2), (0, 2, 0, 1)]);
+    assert_eq!(x_2.substitute(&matrix::<F3_i<1>>([[1, 1, 0], [0, 1, 0], [0, 0, 1]]), &lut), expected);
+  }
+}
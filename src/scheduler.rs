@@ -0,0 +1,185 @@
+use std::collections::VecDeque;
+use std::sync::{atomic::{AtomicUsize, Ordering}, mpsc::Sender, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::algebraic_types::{IsoPolynomial, PolynomialResult};
+use crate::extension_ladder::ExtensionLadder;
+use crate::{is_smooth, CustomChunk};
+
+
+//
+//
+// Work stealing scheduler for the local threads
+// Every thread owns a queue of ranges of classes and takes chunks from the front of it
+// A thread with an empty queue steals half of the last range of the queue with the most classes left
+// It only stops once no classes are left to hand out, a stolen range is briefly in no queue at all
+// Classes differ a lot in cost, singular ones stop at the first singular extension, so chunks are not of a fixed size
+// Every thread measures its time per class and sizes its next chunk to take about TARGET, the results are sorted afterwards
+// A streamed lookup costs about the same for one class as for many, so then chunks are at least STREAMED_CHUNK classes
+//
+//
+
+
+const TARGET: Duration = Duration::from_millis(500);
+const MAX_CHUNK: usize = 1 << 16;
+const STREAMED_CHUNK: usize = 1 << 10;
+
+// First class of the chunk, the points per extension weighted by class size and the results of every class
+pub type ChunkResult<const W: usize> = (usize, (Vec<usize>, Vec<PolynomialResult<W>>));
+
+
+pub struct Scheduler {
+  queues: Vec<Mutex<VecDeque<CustomChunk>>>,
+  // Classes that are not handed out yet, in a queue or being stolen
+  left: AtomicUsize,
+}
+
+fn classes(queue: &VecDeque<CustomChunk>) -> usize {
+  queue.iter().map(|c| c.end - c.start).sum()
+}
+
+impl Scheduler {
+  // Splits the ranges into equally many classes per thread, keeping neighbouring classes together
+  pub fn new(ranges: &[CustomChunk], threads: usize) -> Scheduler {
+    let total: usize = ranges.iter().map(|c| c.end - c.start).sum();
+    let share = total.div_ceil(threads).max(1);
+    let mut queues = vec![VecDeque::new(); threads];
+    let (mut thread, mut filled) = (0, 0);
+    for range in ranges {
+      let mut start = range.start;
+      while start < range.end {
+        let end = range.end.min(start + share - filled);
+        queues[thread].push_back(CustomChunk { start, end });
+        filled += end - start;
+        start = end;
+        if filled == share {
+          thread += 1;
+          filled = 0;
+        }
+      }
+    }
+    Scheduler { queues: queues.into_iter().map(Mutex::new).collect(), left: AtomicUsize::new(total) }
+  }
+
+  // At most size classes from the own queue, stealing when it is empty, None when everything is handed out
+  fn take(&self, thread: usize, size: usize) -> Option<CustomChunk> {
+    loop {
+      {
+        let mut queue = self.queues[thread].lock().unwrap();
+        if let Some(range) = queue.pop_front() {
+          let end = range.end.min(range.start + size);
+          if end < range.end {
+            queue.push_front(CustomChunk { start: end, end: range.end });
+          }
+          self.left.fetch_sub(end - range.start, Ordering::SeqCst);
+          return Some(CustomChunk { start: range.start, end })
+        }
+      }
+      if !self.steal(thread) {
+        return None
+      }
+    }
+  }
+
+  // Tries the other queues from the most classes left down, a victim may have emptied since it was measured
+  // False only when every class is handed out
+  fn steal(&self, thread: usize) -> bool {
+    loop {
+      let mut victims: Vec<(usize, usize)> = (0..self.queues.len())
+        .filter(|&i| i != thread)
+        .map(|i| (classes(&self.queues[i].lock().unwrap()), i))
+        .filter(|&(classes, _)| classes > 0)
+        .collect();
+      victims.sort_unstable_by(|a, b| b.cmp(a));
+
+      for (_, victim) in victims {
+        let stolen = {
+          let mut queue = self.queues[victim].lock().unwrap();
+          match queue.pop_back() {
+            Some(range) if range.end - range.start > 1 => {
+              let middle = range.start + (range.end - range.start) / 2;
+              queue.push_back(CustomChunk { start: range.start, end: middle });
+              CustomChunk { start: middle, end: range.end }
+            },
+            Some(range) => range,
+            None => continue,
+          }
+        };
+        self.queues[thread].lock().unwrap().push_back(stolen);
+        return true
+      }
+
+      if self.left.load(Ordering::SeqCst) == 0 {
+        return false
+      }
+      thread::yield_now();
+    }
+  }
+}
+
+
+// Counts every class handed out by the scheduler, the results of every chunk arrive on tx
+pub fn spawn<const W: usize>(scheduler: Scheduler, iso_polys: Arc<Vec<IsoPolynomial<W>>>, ladder: Arc<ExtensionLadder<W>>, tx: Sender<ChunkResult<W>>) {
+  let threads = scheduler.queues.len();
+  let scheduler = Arc::new(scheduler);
+  for thread in 0..threads {
+    let (scheduler, iso_polys, ladder, tx) = (scheduler.clone(), iso_polys.clone(), ladder.clone(), tx.clone());
+    thread::spawn(move || {
+      // Start small to measure the cost of a class
+      let min_size = if ladder.streams() { STREAMED_CHUNK } else { 1 };
+      let mut size = min_size;
+      while let Some(chunk) = scheduler.take(thread, size) {
+        let start = Instant::now();
+        let result = is_smooth(&iso_polys, chunk.start, chunk.end, &ladder);
+        let per_class = start.elapsed().as_secs_f64() / (chunk.end - chunk.start) as f64;
+        // At most doubling, a single cheap class says little
+        let estimate = if per_class > 0. { (TARGET.as_secs_f64() / per_class) as usize } else { MAX_CHUNK };
+        size = estimate.clamp(min_size, (2 * size).min(MAX_CHUNK));
+        tx.send((chunk.start, result)).unwrap();
+      }
+    });
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Everything the threads are handed out, in the order they got it
+  fn drain(scheduler: &Scheduler, thread: usize, size: usize) -> Vec<CustomChunk> {
+    std::iter::from_fn(|| scheduler.take(thread, size)).collect()
+  }
+
+  #[test]
+  fn steals_half_of_the_fullest_queue() {
+    let scheduler = Scheduler::new(&[CustomChunk { start: 0, end: 4 }, CustomChunk { start: 10, end: 16 }], 3);
+    assert_eq!(scheduler.take(0, 8), Some(CustomChunk { start: 0, end: 4 }));
+    // Thread 1 has 10..14 and thread 2 has 14..16, so thread 0 takes 12..14
+    assert_eq!(scheduler.take(0, 1), Some(CustomChunk { start: 12, end: 13 }));
+    // Then thread 1 steals the back half of 14..16 from thread 2, and then single classes from thread 2 and thread 0
+    assert_eq!(drain(&scheduler, 1, 8), vec![CustomChunk { start: 10, end: 12 }, CustomChunk { start: 15, end: 16 }, CustomChunk { start: 14, end: 15 }, CustomChunk { start: 13, end: 14 }]);
+    assert_eq!(scheduler.take(2, 8), None);
+  }
+
+  // Every class is handed out exactly once, and no thread stops while another queue has classes
+  #[test]
+  fn threads_share_every_class() {
+    let classes = 10_000;
+    let scheduler = Arc::new(Scheduler::new(&[CustomChunk { start: 0, end: classes }], 8));
+    let threads: Vec<_> = (0..8).map(|thread| {
+      let scheduler = scheduler.clone();
+      thread::spawn(move || drain(&scheduler, thread, 1 + thread % 3))
+    }).collect();
+
+    let mut handed_out = vec![false; classes];
+    for chunk in threads.into_iter().flat_map(|t| t.join().unwrap()) {
+      for (class, handed_out) in handed_out[chunk.start..chunk.end].iter_mut().enumerate() {
+        assert!(!std::mem::replace(handed_out, true), "class {} handed out twice", chunk.start + class);
+      }
+    }
+    assert!(handed_out.into_iter().all(|h| h));
+    assert!(scheduler.queues.iter().all(|q| q.lock().unwrap().is_empty()));
+  }
+}
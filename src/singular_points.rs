@@ -0,0 +1,174 @@
+use crate::algebraic_types::Lookup;
+use crate::field_extensions::FieldTraits;
+use crate::matrix::Matrix3;
+use crate::polynomials::{bits_to_constant, substitute_coefficients, Polynomial, Singularity, Term};
+
+
+//
+//
+// Local description of the singular points of a curve, for classification rather than counting
+// A singular point P is moved to (0:0:1) by a matrix M, then f(M (x, y, 1)) is the expansion of f around P
+// Its lowest nonzero homogeneous part has degree the multiplicity m of P and is the tangent cone
+// Elements of F_q^k are written as numbers like the constants in the input files, sum a_i p^i
+// The tangent cone is written like the input polynomials, with c_ab standing for c x^a y^b in the local coordinates
+//
+//
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SingularPoint {
+  // (x:y:z) over F_q^k
+  pub coordinates: String,
+  // Degree of the closed point, so the size of its Frobenius orbit
+  pub degree: usize,
+  pub multiplicity: usize,
+  pub kind: String,
+  pub tangent_cone: String,
+}
+
+impl SingularPoint {
+  pub fn str(&self) -> String {
+    format!("{} | {} | {} | {} | {}", self.coordinates, self.degree, self.multiplicity, self.kind, self.tangent_cone)
+  }
+}
+
+
+fn element_str<F: FieldTraits>(e: F) -> String {
+  bits_to_constant::<F>(e.to_bits()).to_string()
+}
+
+// A matrix whose last column is the point, so it sends (0:0:1) to the point
+fn move_to_origin<F: FieldTraits>((x, y, z): (F, F, F)) -> Matrix3<F> {
+  let (zero, one) = (F::ZERO, F::ONE);
+  if z == one {
+    Matrix3::new([[one, zero, x], [zero, one, y], [zero, zero, one]])
+  } else if y == one {
+    Matrix3::new([[one, zero, x], [zero, zero, one], [zero, one, zero]])
+  } else {
+    Matrix3::new([[zero, zero, one], [zero, one, zero], [one, zero, zero]])
+  }
+}
+
+// Double points are told apart by the discriminant of their tangent cone ax^2 + bxy + cy^2
+fn classify<F: FieldTraits>(multiplicity: usize, cone: &[(usize, F)]) -> String {
+  match multiplicity {
+    2 => {
+      let coefficient = |a: usize| cone.iter().find(|(x, _)| *x == a).map_or(F::ZERO, |(_, c)| *c);
+      let (a, b, c) = (coefficient(2), coefficient(1), coefficient(0));
+      let discriminant = b * b + (a * c).scale(4).neg();
+      if discriminant != F::ZERO {
+        "node".to_owned()
+      } else {
+        "cusp or worse".to_owned()
+      }
+    },
+    3 => "triple point".to_owned(),
+    m => format!("{}-fold point", m),
+  }
+}
+
+// Multiplicity, type and tangent cone of the curve f in the point, f given by its coefficients in F
+fn analyze<F: FieldTraits>(coefficients: &[F], normal: &[Term], point: (F, F, F)) -> (usize, String, String) {
+  let local = substitute_coefficients(coefficients, &move_to_origin(point), normal);
+
+  // In the chart z = 1 around the origin x^a y^b z^c has degree a + b
+  let multiplicity = normal.iter().zip(&local).filter(|(_, &c)| c != F::ZERO).map(|(t, _)| (t.x_deg + t.y_deg) as usize).min().unwrap_or(0);
+  let cone: Vec<(usize, F)> = normal.iter().zip(&local)
+    .filter(|(t, &c)| c != F::ZERO && (t.x_deg + t.y_deg) as usize == multiplicity)
+    .map(|(t, &c)| (t.x_deg as usize, c))
+    .collect();
+  let cone_str: Vec<String> = cone.iter().map(|&(a, c)| format!("{}_{}{}", element_str(c), a, multiplicity - a)).collect();
+  (multiplicity, classify(multiplicity, &cone), cone_str.join(" "))
+}
+
+
+impl<F: FieldTraits> Lookup<F> {
+  // Every singular closed point of exact degree k, without the Euler shortcut
+  pub fn singular_points<const W: usize>(&self, poly: Polynomial<W>, normal: &[Term]) -> Vec<SingularPoint> {
+    let coefficients: Vec<F> = (0..normal.len()).map(|i| self.embedding.embed(poly.packed_coefficient(i, self.embedding.coeff_bits))).collect();
+    let mut count = 0;
+    let mut res = vec![];
+    self.for_each_block(|_, points, table| {
+      for (row, &point) in points.iter().enumerate() {
+        if poly.has_singularity_point(self, table, row, point, &mut count, false) == Singularity::Singular {
+          let (multiplicity, kind, tangent_cone) = analyze(&coefficients, normal, point);
+          res.push(SingularPoint {
+            coordinates: format!("({}:{}:{})", element_str(point.0), element_str(point.1), element_str(point.2)),
+            degree: self.k,
+            multiplicity,
+            kind,
+            tangent_cone,
+          });
+        }
+      }
+      true
+    });
+    res
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::field_extensions::{F2_i, F5_i};
+  use crate::polynomials::tests::polynomial;
+
+  // Singular closed points of exact degree k of the curve, terms as in polynomials::tests::polynomial
+  fn singular_points<B: FieldTraits, F: FieldTraits>(degree: usize, terms: &[(u8, u8, u8, u64)]) -> Vec<SingularPoint> {
+    let (poly, normal) = polynomial::<B>(degree, terms);
+    let (part_x, part_y, part_z) = Polynomial::<4>::generate_derative_luts(&normal, B::CHARACTERISTIC);
+    Lookup::<F>::create::<B>(&normal, &part_x, &part_y, &part_z, None, None).singular_points(poly, &normal)
+  }
+
+  fn point(coordinates: &str, degree: usize, multiplicity: usize, kind: &str, tangent_cone: &str) -> SingularPoint {
+    SingularPoint { coordinates: coordinates.to_owned(), degree, multiplicity, kind: kind.to_owned(), tangent_cone: tangent_cone.to_owned() }
+  }
+
+  // y^2 z = x^3 + x^2 z, locally y^2 - x^2 - x^3 with the two tangents y = x and y = -x
+  #[test]
+  fn nodal_cubic() {
+    let terms = [(0, 2, 1, 1), (3, 0, 0, 4), (2, 0, 1, 4)];
+    assert_eq!(singular_points::<F5_i<1>, F5_i<1>>(3, &terms), vec![point("(0:0:1)", 1, 2, "node", "1_02 4_20")]);
+    assert_eq!(singular_points::<F5_i<1>, F5_i<2>>(3, &terms), vec![]);
+  }
+
+  // y^2 z = x^3, locally y^2 - x^3 with the double tangent y = 0
+  #[test]
+  fn cuspidal_cubic() {
+    let terms = [(0, 2, 1, 1), (3, 0, 0, 4)];
+    assert_eq!(singular_points::<F5_i<1>, F5_i<1>>(3, &terms), vec![point("(0:0:1)", 1, 2, "cusp or worse", "1_02")]);
+  }
+
+  // (x^2 - 2 z^2)^2 + y^3 z, 2 is not a square mod 5 so the only singular points (+-sqrt 2 : 0 : 1) form one closed point of degree 2
+  // Locally around sqrt 2 it is (2 sqrt 2)^2 x^2 + ... = 3 x^2 + ...
+  #[test]
+  fn singular_point_of_degree_2() {
+    let terms = [(4, 0, 0, 1), (2, 0, 2, 1), (0, 0, 4, 4), (0, 3, 1, 1)];
+    assert_eq!(singular_points::<F5_i<1>, F5_i<1>>(4, &terms), vec![]);
+    let points = singular_points::<F5_i<1>, F5_i<2>>(4, &terms);
+    assert_eq!(points.len(), 1);
+    assert!(points[0].coordinates.ends_with(":0:1)"));
+    assert_eq!((points[0].degree, points[0].multiplicity, points[0].kind.as_str(), points[0].tangent_cone.as_str()), (2, 2, "cusp or worse", "3_20"));
+  }
+
+  // Both charts of the line z = 0 in move_to_origin
+  #[test]
+  fn singular_points_at_infinity() {
+    // y z^2 = x^3 + x^2 y has a node in (0:1:0), in the chart y = 1 it is z^2 - x^2 - x^3
+    let terms = [(0, 1, 2, 1), (3, 0, 0, 4), (2, 1, 0, 4)];
+    assert_eq!(singular_points::<F5_i<1>, F5_i<1>>(3, &terms), vec![point("(0:1:0)", 1, 2, "node", "1_02 4_20")]);
+    // x y^2 = z^3 has a cusp in (1:0:0), in the chart x = 1 it is y^2 - z^3
+    let terms = [(1, 2, 0, 1), (0, 0, 3, 4)];
+    assert_eq!(singular_points::<F5_i<1>, F5_i<1>>(3, &terms), vec![point("(1:0:0)", 1, 2, "cusp or worse", "1_02")]);
+    // Over F2 the discriminant of a double point is b^2, x y z has three nodes with tangent cone x y
+    let terms = [(1, 1, 1, 1)];
+    let points = singular_points::<F2_i<1>, F2_i<1>>(3, &terms);
+    let coordinates: Vec<&str> = points.iter().map(|p| p.coordinates.as_str()).collect();
+    assert_eq!(coordinates.len(), 3);
+    for expected in ["(1:0:0)", "(0:1:0)", "(0:0:1)"] {
+      assert!(coordinates.contains(&expected), "{} not in {:?}", expected, coordinates);
+    }
+    assert!(points.iter().all(|p| p.kind == "node" && p.tangent_cone == "1_11"));
+  }
+}
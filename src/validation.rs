@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use crate::algebraic_types::IsoPolynomial;
+use crate::field_extensions::FieldTraits;
+use crate::orbits;
+use crate::polynomials::{field_order, Term};
+
+
+//
+//
+// Validation of an isomorphism class file before any smoothness work is done
+// The classes have to partition all (q^n - 1)/(q - 1) projective polynomials with n = (d+2 choose 2) monomials
+// So their sizes have to add up to that, and no two representatives can lie in the same orbit
+//
+//
+
+
+pub struct ValidationReport {
+  pub classes: usize,
+  pub expected_total: u128,
+  pub total: u128,
+  // Indices of the representatives in the input that share an orbit, every group has at least two
+  pub duplicates: Vec<Vec<usize>>,
+}
+
+impl ValidationReport {
+  pub fn is_valid(&self) -> bool {
+    self.total == self.expected_total && self.duplicates.is_empty()
+  }
+
+  pub fn print<B: FieldTraits, const W: usize>(&self, iso_polys: &[IsoPolynomial<W>], normal: &[Term]) {
+    println!("Validation report");
+    println!("  isomorphism classes: {}", self.classes);
+    println!("  sum of class sizes: {}", self.total);
+    println!("  projective polynomials: {}", self.expected_total);
+    if self.total != self.expected_total {
+      let difference = self.total as i128 - self.expected_total as i128;
+      println!("  class sizes are off by {}", difference);
+    }
+    for group in &self.duplicates {
+      let lines: Vec<String> = group.iter().map(|&i| format!("{} (class {})", iso_polys[i].representative.str::<B>(normal), i)).collect();
+      println!("  same orbit: {}", lines.join(", "));
+    }
+    if self.is_valid() {
+      println!("  file is valid");
+    }
+  }
+}
+
+
+// (q^n - 1)/(q - 1), None when q^n does not fit in 128 bits
+pub fn projective_polynomials(q: u128, n: usize) -> Option<u128> {
+  Some((q.checked_pow(n as u32)? - 1) / (q - 1))
+}
+
+// Whether validate finishes, it applies every element of PGL3 to every class, n * n multiply-adds each
+// The classes cover all projective polynomials, so there are at least that many divided by |PGL3|, like orbits::check_generation
+pub fn check_validation(field_order: usize, degree: usize) -> Result<(), String> {
+  let n = (degree + 2) * (degree + 1) / 2;
+  let total = projective_polynomials(field_order as u128, n)
+    .ok_or(format!("--validate is not supported for degree {} over F{}, there are more than 2^128 polynomials", degree, field_order))?;
+  let order = orbits::pgl3_order(field_order as u64) as u128;
+  let operations = (total / order + 1).saturating_mul(order * (n * n) as u128);
+  if operations > orbits::MAX_OPERATIONS as u128 {
+    return Err(format!("Validating the isomorphism classes of degree {} over F{} takes at least {} operations, too many to finish", degree, field_order, operations))
+  }
+  Ok(())
+}
+
+// Config refuses --validate when check_validation fails
+pub fn validate<B: FieldTraits, const W: usize>(iso_polys: &[IsoPolynomial<W>], normal: &[Term]) -> ValidationReport {
+  let expected_total = projective_polynomials(field_order::<B>() as u128, normal.len()).expect("Too many polynomials to validate");
+  let total = iso_polys.iter().map(|iso| iso.size as u128).sum();
+
+  let representatives: Vec<_> = iso_polys.iter().map(|iso| iso.representative).collect();
+  let mut orbits: HashMap<Vec<u8>, Vec<usize>> = HashMap::new();
+  for (i, canonical) in orbits::canonical_forms::<B, W>(&representatives, normal).into_iter().enumerate() {
+    orbits.entry(canonical).or_default().push(i);
+  }
+  let mut duplicates: Vec<Vec<usize>> = orbits.into_values().filter(|group| group.len() > 1).collect();
+  duplicates.sort();
+
+  ValidationReport { classes: iso_polys.len(), expected_total, total, duplicates }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::field_extensions::F2_i;
+  use crate::matrix::Matrix3;
+  use crate::polynomials::Polynomial;
+
+  fn classes() -> (Vec<IsoPolynomial<1>>, Vec<Term>) {
+    let normal = Polynomial::<1>::generate_default_lut(3);
+    (orbits::generate::<F2_i<1>, 1>(&normal), normal)
+  }
+
+  #[test]
+  fn runs_that_can_not_finish_are_refused() {
+    assert!(check_validation(2, 3).is_ok());
+    assert!(check_validation(3, 4).is_ok());
+    assert!(check_validation(3, 5).is_err());
+    assert!(check_validation(9, 4).is_err());
+    assert!(check_validation(2, 20).is_err());
+  }
+
+  #[test]
+  fn generated_classes_are_valid() {
+    let (iso_polys, normal) = classes();
+    let report = validate::<F2_i<1>, 1>(&iso_polys, &normal);
+    assert!(report.is_valid());
+    assert_eq!((report.total, report.expected_total), (1023, 1023));
+  }
+
+  #[test]
+  fn missing_class_is_found() {
+    let (mut iso_polys, normal) = classes();
+    let missing = iso_polys.remove(3);
+    let report = validate::<F2_i<1>, 1>(&iso_polys, &normal);
+    assert!(!report.is_valid());
+    assert_eq!(report.total, 1023 - missing.size as u128);
+    assert!(report.duplicates.is_empty());
+  }
+
+  // Another representative of a class lies in the same orbit, even with the sizes made to add up again
+  #[test]
+  fn duplicate_class_is_found() {
+    let (mut iso_polys, normal) = classes();
+    let swap = Matrix3::<F2_i<1>>::new([[F2_i::ZERO, F2_i::ONE, F2_i::ZERO], [F2_i::ONE, F2_i::ZERO, F2_i::ZERO], [F2_i::ZERO, F2_i::ZERO, F2_i::ONE]]);
+    let i = iso_polys.iter().position(|iso| iso.representative.substitute(&swap, &normal) != iso.representative).unwrap();
+    let mut duplicate = iso_polys[i];
+    duplicate.representative = duplicate.representative.substitute(&swap, &normal);
+    duplicate.size = 1;
+    iso_polys[i].size -= 1;
+    iso_polys.push(duplicate);
+
+    let report = validate::<F2_i<1>, 1>(&iso_polys, &normal);
+    assert_eq!(report.total, report.expected_total);
+    assert_eq!(report.duplicates, vec![vec![i, iso_polys.len() - 1]]);
+    assert!(!report.is_valid());
+  }
+}
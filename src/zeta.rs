@@ -0,0 +1,183 @@
+//
+//
+// Zeta functions of smooth plane curves from their point counts
+// For a smooth curve of genus g over F_q, Z(T) = P(T) / ((1 - T)(1 - qT)) with P(T) = 1 + a_1 T + ... + a_2g T^2g
+// Writing P(T) = prod (1 - alpha_i T) gives N_k = q^k + 1 - S_k with S_k = sum alpha_i^k
+// Newton's identities k a_k = -(S_1 a_(k-1) + ... + S_k a_0) then give a_1..a_g from N_1..N_g,
+// and the functional equation a_(2g-i) = q^(g-i) a_i gives the rest
+//
+//
+
+
+pub fn genus(degree: usize) -> usize {
+  (degree.max(2) - 1) * (degree.max(2) - 2) / 2
+}
+
+// The most points a smooth curve of genus g can have over F_q^k by the Weil bound, q^k + 1 + 2g q^(k/2)
+// Rounded up, None when it does not fit in 128 bits
+pub fn max_point_count(q: u64, genus: usize, k: usize) -> Option<u128> {
+  let q_k = (q as u128).checked_pow(k as u32)?;
+  let root = q_k.isqrt() + 1;
+  q_k.checked_add(1)?.checked_add((2 * genus as u128).checked_mul(root)?)
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LPolynomial {
+  pub q: i128,
+  // a_0 up to a_2g
+  pub coefficients: Vec<i128>,
+}
+
+impl LPolynomial {
+  pub fn genus(&self) -> usize {
+    (self.coefficients.len() - 1) / 2
+  }
+
+  // Needs the point counts over F_q^k for k = 1..g, returns None if there are too few
+  // or if Newton's identities do not divide out, in which case the counts can't come from a smooth curve of this genus
+  pub fn from_point_counts(q: u64, genus: usize, points_on_curve: &[usize]) -> Option<LPolynomial> {
+    if points_on_curve.len() < genus {
+      return None
+    }
+    let q = q as i128;
+    let power_sums: Vec<i128> = (1..=genus).map(|k| q.pow(k as u32) + 1 - points_on_curve[k - 1] as i128).collect();
+
+    let mut coefficients = vec![0; 2 * genus + 1];
+    coefficients[0] = 1;
+    for k in 1..=genus {
+      let sum: i128 = (1..=k).map(|i| power_sums[i - 1] * coefficients[k - i]).sum();
+      if sum % k as i128 != 0 {
+        return None
+      }
+      coefficients[k] = -sum / k as i128;
+    }
+    for i in 0..genus {
+      coefficients[2 * genus - i] = q.pow((genus - i) as u32) * coefficients[i];
+    }
+    Some(LPolynomial { q, coefficients })
+  }
+
+  // S_k for k = 1..=n, by running Newton's identities the other way around (a_i is zero for i > 2g)
+  // None when an intermediate value overflows
+  fn power_sums(&self, n: usize) -> Option<Vec<i128>> {
+    let a = |i: usize| self.coefficients.get(i).copied().unwrap_or(0);
+    let mut power_sums: Vec<i128> = Vec::with_capacity(n);
+    for k in 1..=n {
+      let sum = (1..k).try_fold(0i128, |acc, i| acc.checked_add(power_sums[i - 1].checked_mul(a(k - i))?))?;
+      power_sums.push((k as i128).checked_mul(a(k))?.checked_neg()?.checked_sub(sum)?);
+    }
+    Some(power_sums)
+  }
+
+  // The amount of points over F_q^k predicted by the L-polynomial, None when it does not fit in 128 bits
+  pub fn point_count(&self, k: usize) -> Option<i128> {
+    self.q.checked_pow(k as u32)?.checked_add(1)?.checked_sub(self.power_sums(k)?[k - 1])
+  }
+
+  // Every point count beyond the genus has to agree with the prediction, which tests the functional equation,
+  // and the counts and coefficients have to respect the Weil bounds |N_k - q^k - 1| <= 2g q^(k/2) and |a_i| <= (2g choose i) q^(i/2)
+  // Both sides of the bounds are squared, a side that does not fit in 128 bits fails the check
+  pub fn check(&self, points_on_curve: &[usize]) -> Result<(), String> {
+    let g = self.genus() as i128;
+    let too_large = |what: String| format!("{} does not fit in 128 bits", what);
+    for (k, &points) in points_on_curve.iter().enumerate().map(|(i, p)| (i + 1, p)) {
+      let predicted = self.point_count(k).ok_or_else(|| too_large(format!("point count over F_q^{}", k)))?;
+      if predicted != points as i128 {
+        return Err(format!("functional equation predicts {} points over F_q^{}, counted {}", predicted, k, points))
+      }
+      let q_k = self.q.checked_pow(k as u32).ok_or_else(|| too_large(format!("q^{}", k)))?;
+      let error = points as i128 - q_k - 1;
+      let bound = (4 * g * g).checked_mul(q_k).ok_or_else(|| too_large(format!("Weil bound over F_q^{}", k)))?;
+      if error.checked_mul(error).is_none_or(|e| e > bound) {
+        return Err(format!("{} points over F_q^{} violates the Weil bound", points, k))
+      }
+    }
+
+    let mut binomial: i128 = 1;
+    for (i, &a) in self.coefficients.iter().enumerate() {
+      let bound = binomial.checked_mul(binomial).and_then(|b| b.checked_mul(self.q.checked_pow(i as u32)?))
+        .ok_or_else(|| too_large(format!("Weil bound of a_{}", i)))?;
+      if a.checked_mul(a).is_none_or(|a| a > bound) {
+        return Err(format!("coefficient a_{} = {} violates the Weil bound", i, a))
+      }
+      binomial = binomial * (2 * g - i as i128) / (i as i128 + 1);
+    }
+    Ok(())
+  }
+
+  pub fn str(&self) -> String {
+    let terms: Vec<String> = self.coefficients.iter().enumerate().filter(|(_, &a)| a != 0).map(|(i, &a)| match i {
+      0 => format!("{}", a),
+      1 => format!("{}T", a),
+      _ => format!("{}T^{}", a, i),
+    }).collect();
+    terms.join(" + ").replace("+ -", "- ")
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::extension_ladder::ExtensionLadder;
+  use crate::field_extensions::{F2, F2_i};
+  use crate::polynomials::{constant_to_bits, Polynomial};
+
+  // y^2 = x^3 - x over F3 is supersingular, P(T) = 1 + 3T^2, so N_k = 3^k + 1 - 2(-3)^(k/2) for even k and 3^k + 1 for odd k
+  #[test]
+  fn supersingular_elliptic_curve() {
+    let l = LPolynomial::from_point_counts(3, 1, &[4]).unwrap();
+    assert_eq!(l.coefficients, vec![1, 0, 3]);
+    assert_eq!((1..=4).map(|k| l.point_count(k).unwrap()).collect::<Vec<_>>(), vec![4, 16, 28, 64]);
+    assert_eq!(l.check(&[4, 16, 28, 64]), Ok(()));
+    assert!(l.check(&[4, 16, 29]).is_err());
+  }
+
+  // The Klein quartic x^3 y + y^3 z + z^3 x over F2 has P(T) = 1 + 5T^3 + 8T^6
+  // Its points are counted by the lookups up to F_64, the first 3 give P and the others have to follow from it
+  #[test]
+  fn klein_quartic() {
+    let args = ["--degree", "4", "--field", "2", "--max-ext", "6", "--input", "unused", "--fresh"];
+    let config = Config::from_args(args.iter().map(|a| a.to_string())).unwrap();
+    let normal = Polynomial::<1>::generate_default_lut(4);
+    let (part_x, part_y, part_z) = Polynomial::<1>::generate_derative_luts(&normal, 2);
+    let bits: Vec<u64> = normal.iter().map(|t| {
+      let klein = [(3, 1, 0), (0, 3, 1), (1, 0, 3)].contains(&(t.x_deg, t.y_deg, t.z_deg));
+      constant_to_bits::<F2_i<1>>(klein as u64)
+    }).collect();
+    let klein = Polynomial::<1>::from_coefficient_bits::<F2_i<1>>(&bits);
+
+    let ladder = ExtensionLadder::<1>::create::<F2>(&config, &normal, &part_x, &part_y, &part_z);
+    let points_on_curve = ladder.points_on_curve(&[klein]).remove(0);
+    assert_eq!(points_on_curve, vec![3, 5, 24, 17, 33, 38]);
+
+    let l = LPolynomial::from_point_counts(2, 3, &points_on_curve).unwrap();
+    assert_eq!(l.coefficients, vec![1, 0, 0, 5, 0, 0, 8]);
+    assert_eq!(l.check(&points_on_curve), Ok(()));
+    // The functional equation a_(2g-i) = q^(g-i) a_i fixes the counts beyond the genus
+    let mut wrong = points_on_curve.clone();
+    wrong[4] += 1;
+    assert!(l.check(&wrong).is_err());
+  }
+
+  // Counts this large overflow the Weil bound, which has to be an error and not a panic
+  #[test]
+  fn check_does_not_overflow() {
+    let l = LPolynomial { q: 1 << 40, coefficients: vec![1, 0, 0, 0, 1 << 80] };
+    assert!(l.check(&[]).is_err());
+    assert!(l.check(&[usize::MAX; 4]).is_err());
+  }
+
+  // The bound rounds q^(k/2) up, and a curve of genus 0 has exactly q^k + 1 points
+  #[test]
+  fn weil_bound_of_point_counts() {
+    assert_eq!(max_point_count(2, 1, 1), Some(7));
+    assert_eq!(max_point_count(3, 0, 2), Some(10));
+    assert_eq!(max_point_count(3, 10, 4), Some(81 + 1 + 20 * 10));
+    assert!(max_point_count(2, 1, 63).is_some_and(|n| n <= u64::MAX as u128));
+    assert!(max_point_count(2, 1, 64).is_some_and(|n| n > u64::MAX as u128));
+    assert_eq!(max_point_count(2, 1, 128), None);
+  }
+}